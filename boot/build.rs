@@ -1,42 +1,83 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use bootloader::{BiosBoot, UefiBoot};
 
+/// Kernel profiles to package; images of the release kernel get a suffix.
+const PROFILES: [(&str, &str); 2] = [("debug", ""), ("release", "-release")];
+
 fn main() {
     let workspace_root = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap())
         .parent()
         .unwrap()
         .to_path_buf();
 
-    let kernel_path = workspace_root
-        .join("target")
-        .join("x86_64-unknown-none")
-        .join("debug")
-        .join("os");
+    // Optional initramfs, handed to the kernel as the bootloader ramdisk
+    let ramdisk = std::env::var_os("OS_INITRAMFS").map(PathBuf::from);
+    if let Some(path) = &ramdisk {
+        if !path.exists() {
+            eprintln!("  [boot] OS_INITRAMFS not found: {}", path.display());
+            std::process::exit(1);
+        }
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+
+    // OS_KERNEL overrides the kernel for the debug-named images
+    let kernels: Vec<(PathBuf, &str)> = match std::env::var_os("OS_KERNEL") {
+        Some(path) => vec![(PathBuf::from(path), "")],
+        None => PROFILES
+            .iter()
+            .map(|(profile, suffix)| {
+                let path = workspace_root
+                    .join("target")
+                    .join("x86_64-unknown-none")
+                    .join(profile)
+                    .join("os");
+                (path, *suffix)
+            })
+            .filter(|(path, _)| path.exists())
+            .collect(),
+    };
 
-    if !kernel_path.exists() {
-        eprintln!("  [boot] Kernel binary not found: {}", kernel_path.display());
-        eprintln!("  [boot] Run: make build");
+    if kernels.is_empty() {
+        eprintln!("  [boot] No kernel binary in target/x86_64-unknown-none/{{debug,release}}");
+        eprintln!("  [boot] Run: make build (or make build-release)");
         std::process::exit(1);
     }
 
+    for (kernel_path, suffix) in &kernels {
+        build_images(&workspace_root, kernel_path, suffix, ramdisk.as_deref());
+    }
+
+    // --- Cargo build triggers ---
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=CARGO_MANIFEST_DIR");
+    println!("cargo:rerun-if-env-changed=OS_INITRAMFS");
+    println!("cargo:rerun-if-env-changed=OS_KERNEL");
+    println!("cargo:rerun-if-changed=../os");
+}
+
+/// Creates `os-bios{suffix}.img` and `os-uefi{suffix}.img` for one kernel.
+fn build_images(workspace_root: &Path, kernel_path: &Path, suffix: &str, ramdisk: Option<&Path>) {
+    eprintln!("  [boot] Kernel: {}", kernel_path.display());
+
     // --- BIOS image ---
-    let bios_img_path = workspace_root.join("os-bios.img");
-    eprintln!("  [boot] Creating BIOS disk image (os-bios.img)...");
-    BiosBoot::new(&kernel_path)
-        .create_disk_image(&bios_img_path)
+    let bios_img_path = workspace_root.join(format!("os-bios{suffix}.img"));
+    eprintln!("  [boot] Creating BIOS disk image (os-bios{suffix}.img)...");
+    let mut bios = BiosBoot::new(kernel_path);
+    if let Some(ramdisk) = ramdisk {
+        bios.set_ramdisk(ramdisk);
+    }
+    bios.create_disk_image(&bios_img_path)
         .expect("failed to create BIOS disk image");
     eprintln!("  [boot] Done: {}", bios_img_path.display());
 
     // --- UEFI image ---
-    let uefi_img_path = workspace_root.join("os-uefi.img");
-    eprintln!("  [boot] Creating UEFI disk image (os-uefi.img)...");
-    UefiBoot::new(&kernel_path)
-        .create_disk_image(&uefi_img_path)
+    let uefi_img_path = workspace_root.join(format!("os-uefi{suffix}.img"));
+    eprintln!("  [boot] Creating UEFI disk image (os-uefi{suffix}.img)...");
+    let mut uefi = UefiBoot::new(kernel_path);
+    if let Some(ramdisk) = ramdisk {
+        uefi.set_ramdisk(ramdisk);
+    }
+    uefi.create_disk_image(&uefi_img_path)
         .expect("failed to create UEFI disk image");
     eprintln!("  [boot] Done: {}", uefi_img_path.display());
-
-    // --- Cargo build triggers ---
-    println!("cargo:rerun-if-changed=build.rs");
-    println!("cargo:rerun-if-env-changed=CARGO_MANIFEST_DIR");
-    println!("cargo:rerun-if-changed=../os");
 }
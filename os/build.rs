@@ -1,3 +1,94 @@
+use std::{env, fs, path::Path, process::Command};
+
 fn main() {
     println!("cargo:rerun-if-changed=linker.ld");
+    write_manifest();
+}
+
+/// Generates `$OUT_DIR/manifest.rs`, included by `kernel::manifest`.
+///
+/// Drivers are every module in `src/drivers` except `mod.rs`, described by
+/// the first paragraph of their `//!` header.
+fn write_manifest() {
+    println!("cargo:rerun-if-changed=src/drivers");
+    watch_git_head();
+
+    let mut drivers = Vec::new();
+    for entry in fs::read_dir("src/drivers").expect("src/drivers") {
+        let path = entry.expect("src/drivers entry").path();
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        if path.extension().is_some_and(|e| e == "rs") && name != "mod" {
+            drivers.push((name, module_summary(&path)));
+        }
+    }
+    drivers.sort();
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| k.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase()))
+        .collect();
+    features.sort();
+
+    let rustc = command_output(&env::var("RUSTC").unwrap_or_else(|_| "rustc".into()), &["--version"]);
+    let git = command_output("git", &["rev-parse", "--short", "HEAD"]);
+
+    let mut out = String::new();
+    out += &format!("pub const VERSION: &str = {:?};\n", env::var("CARGO_PKG_VERSION").unwrap());
+    out += &format!("pub const PROFILE: &str = {:?};\n", env::var("PROFILE").unwrap_or_default());
+    out += &format!("pub const TARGET: &str = {:?};\n", env::var("TARGET").unwrap_or_default());
+    out += &format!("pub const RUSTC: &str = {:?};\n", rustc);
+    out += &format!("pub const GIT_REVISION: &str = {:?};\n", git);
+    out += &format!("pub const FEATURES: &[&str] = &{:?};\n", features);
+    out += "pub const DRIVERS: &[(&str, &str)] = &[\n";
+    for (name, summary) in &drivers {
+        out += &format!("    ({:?}, {:?}),\n", name, summary);
+    }
+    out += "];\n";
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("manifest.rs");
+    fs::write(dest, out).expect("write manifest.rs");
+}
+
+/// Reruns the build script when the checked-out commit changes.
+///
+/// HEAD itself only changes on checkout; a commit moves the branch ref it
+/// points to, which lives in its own file or in `packed-refs`.
+fn watch_git_head() {
+    let git_dir = command_output("git", &["rev-parse", "--absolute-git-dir"]);
+    let git_dir = Path::new(if git_dir == "unknown" { "../.git" } else { &git_dir });
+    let watch = |path: &Path| {
+        // A missing path would make cargo rerun the script on every build
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    };
+
+    let head = git_dir.join("HEAD");
+    watch(&head);
+    watch(&git_dir.join("packed-refs"));
+    if let Some(reference) = fs::read_to_string(&head).ok().as_deref().and_then(|h| h.strip_prefix("ref: ")) {
+        watch(&git_dir.join(reference.trim()));
+    }
+}
+
+/// First paragraph of a module's `//!` doc, on one line.
+fn module_summary(path: &Path) -> String {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    source
+        .lines()
+        .map_while(|l| l.strip_prefix("//!"))
+        .map(str::trim)
+        .take_while(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Trimmed stdout of a command, or "unknown" if it cannot run.
+fn command_output(program: &str, args: &[&str]) -> String {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".into())
 }
@@ -0,0 +1,137 @@
+//! Control-flow Enforcement Technology (CET)
+//!
+//! - Indirect branch tracking (IBT): every indirect call/jump target, and
+//!   every interrupt handler, must start with `endbr64`, or the CPU raises
+//!   #CP. Enabled only if the whole image carries those markers, which
+//!   needs `-Z cf-protection=branch` together with `-Z build-std=core`:
+//!   the prebuilt `core` from the toolchain has no `endbr64`, and the
+//!   first indirect call into it (formatting) would fault. Both are
+//!   checked on the image itself, so any other build simply keeps IBT off.
+//! - Supervisor shadow stacks: detected and reported only. Enabling them
+//!   needs shadow-stack pages (a PTE encoding the paging code cannot
+//!   produce yet) and an interrupt SSP table for the IST stacks.
+//!
+//! Any #CP is a control-flow violation and goes to the panic path.
+
+use x86_64::registers::{
+    control::{Cr0, Cr0Flags, Cr4, Cr4Flags},
+    model_specific::Msr,
+};
+use x86_64::structures::idt::InterruptStackFrame;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::serial;
+
+const IA32_S_CET: u32 = 0x6A2;
+const S_CET_ENDBR_EN: u64 = 1 << 2;
+
+/// `endbr64` encoding
+const ENDBR64: [u8; 4] = [0xF3, 0x0F, 0x1E, 0xFA];
+
+/// #CP error codes (low 15 bits)
+const CP_NEAR_RET: u64 = 1;
+const CP_FAR_RET_IRET: u64 = 2;
+const CP_ENDBRANCH: u64 = 3;
+const CP_RSTORSSP: u64 = 4;
+const CP_SETSSBSY: u64 = 5;
+
+static IBT_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// CET features reported by CPUID.(EAX=7,ECX=0)
+#[derive(Debug, Clone, Copy)]
+pub struct CetSupport {
+    /// Shadow stacks (ECX bit 7)
+    pub shadow_stack: bool,
+    /// Indirect branch tracking (EDX bit 20)
+    pub ibt: bool,
+}
+
+pub fn support() -> CetSupport {
+    let max_leaf = core::arch::x86_64::__cpuid(0).eax;
+    if max_leaf < 7 {
+        return CetSupport { shadow_stack: false, ibt: false };
+    }
+    let leaf = core::arch::x86_64::__cpuid_count(7, 0);
+    CetSupport {
+        shadow_stack: leaf.ecx & (1 << 7) != 0,
+        ibt: leaf.edx & (1 << 20) != 0,
+    }
+}
+
+/// True if interrupt handlers in this kernel image, and `core` linked into
+/// it, start with `endbr64`.
+fn image_has_endbr() -> bool {
+    use crate::arch::x86::idt::handlers;
+    use core::fmt::{Display, Formatter, Result};
+    let targets = [
+        handlers::timer_handler as *const u8,
+        handlers::page_fault_handler as *const u8,
+        control_protection_handler as *const u8,
+        // Reached through `dyn Display`: only marked if `core` was rebuilt
+        <u64 as Display>::fmt as fn(&u64, &mut Formatter) -> Result as *const u8,
+    ];
+    targets.iter().all(|&f| {
+        // SAFETY: reading the first bytes of kernel code
+        let bytes = unsafe { core::ptr::read_unaligned(f as *const [u8; 4]) };
+        bytes == ENDBR64
+    })
+}
+
+/// Returns true once IBT is active.
+pub fn ibt_enabled() -> bool {
+    IBT_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Detects CET and enables IBT when both CPU and kernel image support it.
+///
+/// Call after the IDT (with the #CP handler) is loaded.
+pub fn init() {
+    let cet = support();
+    serial::write_fmt(format_args!(
+        "cet: shadow stack {}, IBT {}\n",
+        if cet.shadow_stack { "supported (not enabled)" } else { "no" },
+        if cet.ibt { "supported" } else { "no" }
+    ));
+
+    if !cet.ibt {
+        return;
+    }
+    if !image_has_endbr() {
+        serial::write_str("cet: kernel or core built without endbr64, IBT stays off\n");
+        return;
+    }
+    // CR4.CET may only be set with CR0.WP
+    if !Cr0::read().contains(Cr0Flags::WRITE_PROTECT) {
+        serial::write_str("cet: CR0.WP clear, IBT stays off\n");
+        return;
+    }
+
+    unsafe {
+        Cr4::update(|f| f.insert(Cr4Flags::CONTROL_FLOW_ENFORCEMENT));
+        let mut s_cet = Msr::new(IA32_S_CET);
+        let value = s_cet.read();
+        s_cet.write(value | S_CET_ENDBR_EN);
+    }
+    IBT_ENABLED.store(true, Ordering::Relaxed);
+    serial::write_str("cet: IBT enabled\n");
+}
+
+fn cp_reason(error_code: u64) -> &'static str {
+    match error_code & 0x7FFF {
+        CP_NEAR_RET => "near RET",
+        CP_FAR_RET_IRET => "far RET/IRET",
+        CP_ENDBRANCH => "missing ENDBRANCH",
+        CP_RSTORSSP => "RSTORSSP",
+        CP_SETSSBSY => "SETSSBSY",
+        _ => "unknown",
+    }
+}
+
+/// #CP (vector 21): control-flow violation
+pub extern "x86-interrupt" fn control_protection_handler(frame: InterruptStackFrame, error_code: u64) {
+    panic!(
+        "control protection fault ({}) at RIP={:#x}, ERR={:#x}",
+        cp_reason(error_code),
+        frame.instruction_pointer.as_u64(),
+        error_code
+    );
+}
@@ -0,0 +1,71 @@
+//! Exception fixup table
+//!
+//! Code that may fault on purpose (copies from user memory, MMIO probes)
+//! records `(faulting instruction, fixup)` pairs in the `__ex_table`
+//! section. The #PF and #GP handlers look up the faulting RIP here and, on a
+//! hit, resume at the fixup instead of treating the fault as fatal. The
+//! fixup then returns an error to its caller.
+//!
+//! Entries are emitted from assembly:
+//! ```text
+//! .pushsection __ex_table, "a"
+//! .balign 8
+//! .quad <insn>, <fixup>
+//! .popsection
+//! ```
+//! `linker.ld` collects them between `__ex_table_start` and `__ex_table_end`.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::structures::idt::InterruptStackFrame;
+
+/// One fixup record (layout shared with the assembly above)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExceptionTableEntry {
+    /// Address of the instruction allowed to fault
+    pub insn: u64,
+    /// Address to resume at when it does
+    pub fixup: u64,
+}
+
+extern "C" {
+    static __ex_table_start: ExceptionTableEntry;
+    static __ex_table_end: ExceptionTableEntry;
+}
+
+/// Faults recovered through the table since boot
+pub static FIXUP_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Returns all entries linked into the kernel.
+pub fn entries() -> &'static [ExceptionTableEntry] {
+    unsafe {
+        let start = &raw const __ex_table_start;
+        let end = &raw const __ex_table_end;
+        let len = (end as usize - start as usize) / core::mem::size_of::<ExceptionTableEntry>();
+        core::slice::from_raw_parts(start, len)
+    }
+}
+
+/// Returns the fixup address registered for `rip`, if any.
+pub fn search(rip: u64) -> Option<u64> {
+    entries().iter().find(|e| e.insn == rip).map(|e| e.fixup)
+}
+
+/// Redirects `frame` to the fixup for its RIP.
+///
+/// Returns false (frame untouched) if the faulting instruction has no
+/// entry, in which case the fault is a genuine kernel bug.
+pub fn try_fixup(frame: &mut InterruptStackFrame) -> bool {
+    let Some(fixup) = search(frame.instruction_pointer.as_u64()) else {
+        return false;
+    };
+
+    // SAFETY: fixup addresses come from the kernel's own exception table
+    unsafe {
+        frame
+            .as_mut()
+            .update(|f| f.instruction_pointer = x86_64::VirtAddr::new(fixup));
+    }
+    FIXUP_COUNT.fetch_add(1, Ordering::Relaxed);
+    true
+}
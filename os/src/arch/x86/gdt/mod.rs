@@ -34,6 +34,9 @@ use crate::serial;
 pub fn init() {
     serial::write_str("=== GDT Initialization ===\n");
     
+    // Canary-fill stacks while nothing runs on them
+    stack::paint_all();
+
     // Initialize TSS with stack pointers
     tss::init();
     
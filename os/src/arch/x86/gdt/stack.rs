@@ -2,8 +2,30 @@
 //!
 //! This module defines and manages the kernel's execution stacks.
 //! Each stack is 16-byte aligned and placed in the .bss section.
+//!
+//! # Usage tracking
+//!
+//! At boot every stack is painted with `STACK_CANARY`. Stack memory is
+//! never cleaned up, so the lowest overwritten word marks the deepest the
+//! stack has ever reached (its high-water mark). `check_all` runs
+//! periodically and warns once per stack when less than `LOW_WATER_WARN`
+//! bytes were left untouched.
+//!
+//! The kernel runs on the stack the bootloader set up
+//! (`BootloaderConfig::kernel_stack_size`, with a guard page below it).
+//! `track_boot_stack` paints the part below the current frame at boot, so
+//! it gets a high-water mark like the static stacks. Those are only
+//! entered through the TSS (ring-3 entry, IST), so theirs stay near 0
+//! until that happens.
 
 use super::STACK_SIZE;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Pattern written over stack memory before first use
+pub const STACK_CANARY: u64 = 0x5354_4B43_5354_4B43;
+
+/// Remaining untouched bytes below which `check_all` warns
+pub const LOW_WATER_WARN: usize = 4 * 1024;
 
 /// Aligned stack structure
 ///
@@ -25,6 +47,52 @@ impl Stack {
     pub fn top_ptr(&self) -> *const u8 {
         unsafe { self.0.as_ptr().add(STACK_SIZE) }
     }
+
+    /// Fill the whole stack with `STACK_CANARY`
+    ///
+    /// # Safety
+    /// The stack must not be in use (not loaded in RSP, no IST entry live).
+    pub unsafe fn paint(&mut self) {
+        paint_words(self.0.as_mut_ptr() as *mut u64, STACK_SIZE / 8);
+    }
+
+    /// Deepest usage in bytes since `paint`
+    pub fn high_water(&self) -> usize {
+        // SAFETY: the whole array is readable
+        unsafe { high_water(self.0.as_ptr() as *const u64, STACK_SIZE) }
+    }
+}
+
+unsafe fn paint_words(words: *mut u64, count: usize) {
+    for i in 0..count {
+        words.add(i).write_volatile(STACK_CANARY);
+    }
+}
+
+/// Bytes of `[base, base + size)` overwritten since it was painted
+///
+/// Scans up from the base for the first overwritten word. Volatile reads:
+/// the owning context may be running on it concurrently.
+unsafe fn high_water(base: *const u64, size: usize) -> usize {
+    let untouched = (0..size / 8)
+        .take_while(|&i| unsafe { base.add(i).read_volatile() } == STACK_CANARY)
+        .count();
+    size - untouched * 8
+}
+
+/// Usage snapshot of one stack
+#[derive(Debug, Clone, Copy)]
+pub struct StackUsage {
+    pub name: &'static str,
+    pub size: usize,
+    pub high_water: usize,
+}
+
+impl StackUsage {
+    /// Bytes never touched
+    pub fn free(&self) -> usize {
+        self.size - self.high_water
+    }
 }
 
 // === Kernel Stacks ===
@@ -103,3 +171,96 @@ pub fn log_stack_info() {
         crate::serial::write_str("\n");
     }
 }
+
+/// Stacks whose low-water warning was already printed (bit per stack)
+static WARNED: AtomicU8 = AtomicU8::new(0);
+
+/// Bootloader stack, recorded by `track_boot_stack` (0 = not tracked)
+static BOOT_STACK_BOTTOM: AtomicU64 = AtomicU64::new(0);
+static BOOT_STACK_LEN: AtomicU64 = AtomicU64::new(0);
+
+/// Room left above the painted area for this function's own frame
+const PAINT_MARGIN: u64 = 256;
+
+/// Paint the unused part of the bootloader stack and track it from now on
+///
+/// `bottom` and `len` come from `BootInfo::kernel_stack_bottom` and
+/// `kernel_stack_len`. Call early and with interrupts disabled: every word
+/// below the current stack pointer is overwritten.
+#[inline(never)]
+pub fn track_boot_stack(bottom: u64, len: u64) {
+    let rsp: u64;
+    // SAFETY: reads RSP only
+    unsafe { core::arch::asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack)) };
+    if bottom == 0 || !(bottom..bottom + len).contains(&rsp) {
+        crate::serial::write_str("stack: not on the bootloader stack, not tracked\n");
+        return;
+    }
+    let painted = (rsp - PAINT_MARGIN).saturating_sub(bottom) / 8;
+    // SAFETY: [bottom, rsp - margin) is mapped stack memory below every
+    // live frame; no interrupt can push onto it meanwhile
+    unsafe { paint_words(bottom as *mut u64, painted as usize) };
+    BOOT_STACK_LEN.store(len, Ordering::Relaxed);
+    BOOT_STACK_BOTTOM.store(bottom, Ordering::Relaxed);
+}
+
+/// Paint all kernel stacks with the canary
+///
+/// Must run before the TSS points at them (i.e. before `tss::init`).
+pub fn paint_all() {
+    unsafe {
+        for stack in [&raw mut KERNEL_STACK, &raw mut INTERRUPT_STACK, &raw mut DOUBLE_FAULT_STACK] {
+            (*stack).paint();
+        }
+    }
+}
+
+/// High-water marks of all tracked kernel stacks (the boot stack first,
+/// if `track_boot_stack` accepted it)
+pub fn usage() -> impl Iterator<Item = StackUsage> {
+    let bottom = BOOT_STACK_BOTTOM.load(Ordering::Relaxed);
+    let len = BOOT_STACK_LEN.load(Ordering::Relaxed) as usize;
+    let boot = (bottom != 0).then(|| StackUsage {
+        name: "boot",
+        size: len,
+        // SAFETY: recorded by track_boot_stack; the stack stays mapped
+        high_water: unsafe { high_water(bottom as *const u64, len) },
+    });
+
+    let statics = unsafe {
+        [
+            ("kernel", &raw const KERNEL_STACK),
+            ("interrupt", &raw const INTERRUPT_STACK),
+            ("double fault", &raw const DOUBLE_FAULT_STACK),
+        ]
+        .map(|(name, stack)| StackUsage {
+            name,
+            size: STACK_SIZE,
+            high_water: (*stack).high_water(),
+        })
+    };
+    boot.into_iter().chain(statics)
+}
+
+/// Warn (once per stack) about stacks close to overflowing
+pub fn check_all() {
+    for (i, stack) in usage().enumerate() {
+        let bit = 1u8 << i;
+        if stack.free() < LOW_WATER_WARN && WARNED.fetch_or(bit, Ordering::Relaxed) & bit == 0 {
+            crate::kernel::log::log_warn!(
+                "stack: {} stack high-water {} of {} bytes",
+                stack.name, stack.high_water, stack.size
+            );
+        }
+    }
+}
+
+/// Log high-water marks of all kernel stacks
+pub fn log_usage() {
+    for stack in usage() {
+        crate::serial::write_fmt(format_args!(
+            "  {:<12} {:>6} / {} bytes\n",
+            stack.name, stack.high_water, stack.size
+        ));
+    }
+}
@@ -0,0 +1,38 @@
+//! x86 implementation of the kernel HAL traits.
+//!
+//! Thin forwarding to the PIT, the PIC/LAPIC EOI path and COM1; IRQ
+//! routing between PIC and LAPIC stays in `pic`.
+
+use crate::arch::x86::{pic, pit};
+use crate::kernel::hal::{InterruptController, Timer, Uart};
+
+/// The running machine
+#[derive(Debug, Clone, Copy)]
+pub struct X86;
+
+impl Timer for X86 {
+    fn tick_hz(&self) -> u32 {
+        pit::TICK_HZ
+    }
+
+    fn delay_ms(&self, ms: u32) {
+        pit::delay_ms(ms)
+    }
+}
+
+impl InterruptController for X86 {
+    fn unmask(&self, irq: u8) {
+        pic::unmask(irq)
+    }
+
+    fn end_of_interrupt(&self, irq: u8) {
+        pic::notify_end_of_interrupt(irq)
+    }
+}
+
+impl Uart for X86 {
+    #[inline]
+    fn write_byte(&self, byte: u8) {
+        crate::serial::write_byte(byte)
+    }
+}
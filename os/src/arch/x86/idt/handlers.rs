@@ -1,13 +1,15 @@
 use x86_64::structures::idt::{InterruptStackFrame, PageFaultErrorCode};
 use x86_64::registers::control::Cr2;
 use crate::arch::x86::idt::storage::*;
-use crate::arch::x86::pic;
+use crate::arch::x86::{extable, hal::X86, pic};
+use crate::kernel::{preempt, tick};
+use crate::kernel::log::{ratelimit::log_ratelimited, Level};
 use core::sync::atomic::Ordering;
 
 // === Exception handlers ===
 pub extern "x86-interrupt" fn divide_error_handler(_frame: InterruptStackFrame) {
     DIV_COUNT.fetch_add(1, Ordering::SeqCst);
-    crate::serial::write_str("=== DIVIDE ERROR ===\n");
+    log_ratelimited!(Level::Error, "=== DIVIDE ERROR ===");
 }
 
 pub extern "x86-interrupt" fn double_fault_handler(
@@ -29,23 +31,28 @@ pub extern "x86-interrupt" fn double_fault_handler(
 }
 
 pub extern "x86-interrupt" fn invalid_tss_handler(_frame: InterruptStackFrame, _error_code: u64) {
-    crate::serial::write_str("=== INVALID TSS ===\n");
+    log_ratelimited!(Level::Error, "=== INVALID TSS ===");
 }
 
 pub extern "x86-interrupt" fn segment_not_present_handler(_frame: InterruptStackFrame, _error_code: u64) {
-    crate::serial::write_str("=== SEGMENT NOT PRESENT ===\n");
+    log_ratelimited!(Level::Error, "=== SEGMENT NOT PRESENT ===");
 }
 
 pub extern "x86-interrupt" fn stack_segment_fault_handler(_frame: InterruptStackFrame, _error_code: u64) {
-    crate::serial::write_str("=== STACK SEGMENT FAULT ===\n");
+    log_ratelimited!(Level::Error, "=== STACK SEGMENT FAULT ===");
 }
 
 pub extern "x86-interrupt" fn general_protection_handler(
-    frame: InterruptStackFrame,
+    mut frame: InterruptStackFrame,
     error_code: u64,
 ) {
     GP_COUNT.fetch_add(1, Ordering::SeqCst);
 
+    // Non-canonical user pointers fault with #GP rather than #PF
+    if extable::try_fixup(&mut frame) {
+        return;
+    }
+
     crate::serial::write_str("\n=== GENERAL PROTECTION FAULT ===\n");
     crate::serial::write_str("RIP="); crate::serial::write_u64_hex(frame.instruction_pointer.as_u64());
     crate::serial::write_str("ERR="); crate::serial::write_u64_hex(error_code);
@@ -54,16 +61,21 @@ pub extern "x86-interrupt" fn general_protection_handler(
 }
 
 pub extern "x86-interrupt" fn breakpoint_handler(_frame: InterruptStackFrame) {
-    crate::serial::write_str("=== BREAKPOINT ===\n");
+    log_ratelimited!(Level::Info, "=== BREAKPOINT ===");
 }
 
 // === Page fault handler ===
 pub extern "x86-interrupt" fn page_fault_handler(
-    frame: InterruptStackFrame,
+    mut frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
     PF_COUNT.fetch_add(1, Ordering::SeqCst);
 
+    // Expected fault in usercopy/probe code: resume at its fixup
+    if extable::try_fixup(&mut frame) {
+        return;
+    }
+
     let fault_addr = Cr2::read().expect("CR2 read failed");
 
     crate::serial::write_str("\n=== PAGE FAULT ===\n");
@@ -81,20 +93,18 @@ pub extern "x86-interrupt" fn page_fault_handler(
 
 // === Timer handler ===
 pub extern "x86-interrupt" fn timer_handler(_frame: InterruptStackFrame) {
-    on_timer_tick();
-    pic::notify_end_of_interrupt(pic::IRQ_TIMER);
-}
-
-fn on_timer_tick() {
-    let n = TICK_COUNT.fetch_add(1, Ordering::SeqCst);
-    if (n + 1) % TICKS_PER_DOT == 0 {
-        crate::serial::write_byte(b'.');
-    }
+    let _irq = preempt::irq_enter();
+    crate::rand::add_interrupt_timing(pic::TIMER_VECTOR);
+    tick::on_tick(&TICK_COUNT, pic::IRQ_TIMER, &X86, &X86);
 }
 
 // === Keyboard IRQ ===
 pub extern "x86-interrupt" fn keyboard_handler(_frame: InterruptStackFrame) {
-    crate::serial::write_str("=== KEYBOARD IRQ ===\n");
+    // Not nestable: like the timer it writes to the unlocked COM1 (log
+    // sinks vs. the heartbeat), so the timer must not interrupt it.
+    let _irq = preempt::irq_enter();
+    crate::rand::add_interrupt_timing(pic::KEYBOARD_VECTOR);
+    crate::drivers::ps2_keyboard::handle_irq();
     pic::notify_end_of_interrupt(pic::IRQ_KEYBOARD);
 }
 
@@ -102,7 +112,7 @@ pub extern "x86-interrupt" fn keyboard_handler(_frame: InterruptStackFrame) {
 macro_rules! stub {
     ($name:ident) => {
         pub extern "x86-interrupt" fn $name(_frame: InterruptStackFrame) {
-            crate::serial::write_str(concat!("=== ", stringify!($name), " ===\n"));
+            log_ratelimited!(Level::Warn, concat!("=== ", stringify!($name), " ==="));
         }
     };
 }
@@ -115,8 +125,34 @@ stub!(bound_range_handler);
 stub!(invalid_opcode_handler);
 stub!(device_not_available_handler);
 
+// === Spurious interrupts ===
+fn pic_spurious(irq: u8) {
+    if pic::is_spurious(irq) {
+        SPURIOUS_PIC_COUNT.fetch_add(1, Ordering::Relaxed);
+        pic::notify_spurious(irq);
+    } else {
+        log_ratelimited!(Level::Warn, "=== UNEXPECTED IRQ {} ===", irq);
+        pic::notify_end_of_interrupt(irq);
+    }
+}
+
+pub extern "x86-interrupt" fn pic_spurious_master_handler(_frame: InterruptStackFrame) {
+    pic_spurious(pic::IRQ_SPURIOUS_MASTER);
+}
+
+pub extern "x86-interrupt" fn pic_spurious_slave_handler(_frame: InterruptStackFrame) {
+    pic_spurious(pic::IRQ_SPURIOUS_SLAVE);
+}
+
+/// LAPIC spurious vector: never acknowledged with an EOI
+pub extern "x86-interrupt" fn apic_spurious_handler(_frame: InterruptStackFrame) {
+    SPURIOUS_APIC_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
 // === Generic unexpected handler ===
 pub extern "x86-interrupt" fn unexpected_interrupt_handler(_frame: InterruptStackFrame) {
-    crate::serial::write_str("=== UNEXPECTED INTERRUPT ===\n");
-    pic::notify_end_of_interrupt(pic::IRQ_UNKNOWN);
+    UNEXPECTED_COUNT.fetch_add(1, Ordering::Relaxed);
+    log_ratelimited!(Level::Warn, "=== UNEXPECTED INTERRUPT ===");
+    // Only acknowledge what the controller actually has in service
+    pic::eoi_in_service();
 }
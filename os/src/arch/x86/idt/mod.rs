@@ -10,6 +10,7 @@ use crate::arch::x86::idt::handlers::*;
 use crate::arch::x86::idt::storage::*;
 use x86_64::structures::idt::InterruptDescriptorTable;
 use crate::arch::x86::gdt::DF_IST_INDEX;
+use crate::arch::x86::{lapic, pic};
 use crate::serial;
 
 /// Initialize Interrupt Descriptor Table
@@ -26,6 +27,7 @@ pub fn init() {
         install_exception_handlers(idt);
         install_irq_handlers(idt);
         install_default_handlers(idt);
+        install_spurious_handlers(idt);
         
         serial::write_str("Loading IDT...\n");
         idt.load();
@@ -57,14 +59,16 @@ unsafe fn install_exception_handlers(idt: &mut InterruptDescriptorTable) {
     idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);      // 12: #SS
     idt.general_protection_fault.set_handler_fn(general_protection_handler);  // 13: #GP
     idt.page_fault.set_handler_fn(page_fault_handler);                        // 14: #PF
+    idt.cp_protection_exception
+        .set_handler_fn(crate::arch::x86::cet::control_protection_handler);  // 21: #CP
 }
 
 /// Install hardware IRQ handlers (vectors 32-47)
 unsafe fn install_irq_handlers(idt: &mut InterruptDescriptorTable) {
     serial::write_str("Installing IRQ handlers...\n");
     
-    idt[32].set_handler_fn(timer_handler);       // IRQ0: PIT Timer
-    idt[33].set_handler_fn(keyboard_handler);    // IRQ1: PS/2 Keyboard
+    idt[pic::TIMER_VECTOR].set_handler_fn(timer_handler);       // IRQ0: PIT Timer
+    idt[pic::KEYBOARD_VECTOR].set_handler_fn(keyboard_handler); // IRQ1: PS/2 Keyboard
 }
 
 /// Install default handler for remaining vectors
@@ -81,3 +85,10 @@ unsafe fn install_default_handlers(idt: &mut InterruptDescriptorTable) {
         idt[vector].set_handler_fn(unexpected_interrupt_handler);
     }
 }
+
+/// Install spurious interrupt handlers over the defaults
+unsafe fn install_spurious_handlers(idt: &mut InterruptDescriptorTable) {
+    idt[39].set_handler_fn(pic_spurious_master_handler);            // IRQ7
+    idt[47].set_handler_fn(pic_spurious_slave_handler);             // IRQ15
+    idt[lapic::SPURIOUS_VECTOR].set_handler_fn(apic_spurious_handler);
+}
@@ -9,9 +9,13 @@ pub static DF_COUNT: AtomicU64 = AtomicU64::new(0);
 pub static PF_COUNT: AtomicU64 = AtomicU64::new(0);
 pub static GP_COUNT: AtomicU64 = AtomicU64::new(0);
 
+// === Spurious interrupt counters (no EOI sent) ===
+pub static SPURIOUS_PIC_COUNT: AtomicU64 = AtomicU64::new(0);
+pub static SPURIOUS_APIC_COUNT: AtomicU64 = AtomicU64::new(0);
+pub static UNEXPECTED_COUNT: AtomicU64 = AtomicU64::new(0);
+
 // === Timer tick counter ===
 pub static TICK_COUNT: AtomicU64 = AtomicU64::new(0);
-pub const TICKS_PER_DOT: u64 = 10;
 
 // === Global IDT Storage ===
 #[repr(align(16))]
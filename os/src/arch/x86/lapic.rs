@@ -0,0 +1,160 @@
+//! Local APIC (xAPIC, memory-mapped).
+//!
+//! Used when the kernel runs with `pic=off`: both 8259s are masked, the tick
+//! comes from the LAPIC timer on the PIT's old vector (32) and EOIs go to
+//! the LAPIC. Registers are reached through the bootloader's physical memory
+//! window; the first access is probed so a window that does not cover the
+//! APIC page makes `init` fail instead of faulting.
+//!
+//! Legacy device IRQs (keyboard) reach the LAPIC only through an I/O APIC,
+//! which is not supported yet, so in this mode only the timer is delivered.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::{registers::model_specific::Msr, VirtAddr};
+use crate::arch::x86::{pit, usercopy};
+
+const IA32_APIC_BASE: u32 = 0x1B;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+const APIC_BASE_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+const REG_ID: u64 = 0x020;
+const REG_VERSION: u64 = 0x030;
+const REG_TPR: u64 = 0x080;
+const REG_EOI: u64 = 0x0B0;
+const REG_SVR: u64 = 0x0F0;
+const REG_ISR_BASE: u64 = 0x100;
+const REG_LVT_TIMER: u64 = 0x320;
+const REG_TIMER_INIT: u64 = 0x380;
+const REG_TIMER_CURRENT: u64 = 0x390;
+const REG_TIMER_DIVIDE: u64 = 0x3E0;
+
+const SVR_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIVIDE_16: u32 = 0x3;
+
+/// Vector raised by the LAPIC timer (shared with PIT IRQ0)
+pub const TIMER_VECTOR: u8 = 32;
+
+/// Spurious interrupt vector programmed into the SVR
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+
+/// Length of the PIT-timed calibration window
+const CALIBRATION_MS: u32 = 10;
+
+/// Virtual address of the register page (0 = not initialized)
+static BASE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LapicError {
+    /// CPUID reports no APIC
+    NotSupported,
+    /// Register page is not reachable through the physical memory window
+    NotMapped,
+    /// Register page already claimed in the resource tree
+    Busy,
+}
+
+#[inline(always)]
+fn read(reg: u64) -> u32 {
+    unsafe { core::ptr::read_volatile((BASE.load(Ordering::Relaxed) + reg) as *const u32) }
+}
+
+#[inline(always)]
+fn write(reg: u64, value: u32) {
+    unsafe { core::ptr::write_volatile((BASE.load(Ordering::Relaxed) + reg) as *mut u32, value) }
+}
+
+/// Returns true if CPUID.1:EDX reports an on-chip APIC.
+pub fn is_supported() -> bool {
+    let leaf = core::arch::x86_64::__cpuid(1);
+    leaf.edx & (1 << 9) != 0
+}
+
+/// Returns true once `init` has succeeded.
+pub fn is_enabled() -> bool {
+    BASE.load(Ordering::Relaxed) != 0
+}
+
+/// Enables the local APIC and sets the spurious vector.
+///
+/// The timer stays off until `start_timer`.
+///
+/// # Safety
+/// `phys_offset` must be the bootloader's physical memory mapping offset.
+/// The IDT must be loaded (the probe relies on the #PF fixup).
+pub unsafe fn init(phys_offset: VirtAddr) -> Result<(), LapicError> {
+    if !is_supported() {
+        return Err(LapicError::NotSupported);
+    }
+
+    let mut msr = Msr::new(IA32_APIC_BASE);
+    let mut apic_base = msr.read();
+    if apic_base & APIC_BASE_ENABLE == 0 {
+        apic_base |= APIC_BASE_ENABLE;
+        msr.write(apic_base);
+    }
+
+    let phys = apic_base & APIC_BASE_ADDR_MASK;
+    if let Err(e) = crate::kernel::resource::request_mem(phys, 0x1000, "Local APIC") {
+        crate::kernel::resource::log_conflict("lapic", e);
+        return Err(LapicError::Busy);
+    }
+    let base = phys_offset.as_u64() + phys;
+    if usercopy::probe_read_u32((base + REG_VERSION) as *const u32).is_none() {
+        return Err(LapicError::NotMapped);
+    }
+    BASE.store(base, Ordering::Relaxed);
+
+    // Accept every priority, then software-enable
+    write(REG_TPR, 0);
+    write(REG_SVR, SVR_ENABLE | SPURIOUS_VECTOR as u32);
+    Ok(())
+}
+
+/// APIC ID of the current CPU.
+pub fn id() -> u32 {
+    read(REG_ID) >> 24
+}
+
+/// Version field of the version register.
+pub fn version() -> u32 {
+    read(REG_VERSION) & 0xFF
+}
+
+/// Starts the periodic timer on `TIMER_VECTOR` at `hz`.
+///
+/// The timer rate is unknown, so it is first measured against
+/// `pit::delay_ms`. Returns the initial count per period.
+/// Call with interrupts disabled.
+pub fn start_timer(hz: u32) -> u32 {
+    write(REG_TIMER_DIVIDE, TIMER_DIVIDE_16);
+    write(REG_LVT_TIMER, LVT_MASKED | TIMER_VECTOR as u32);
+    write(REG_TIMER_INIT, u32::MAX);
+    pit::delay_ms(CALIBRATION_MS);
+    let elapsed = u32::MAX - read(REG_TIMER_CURRENT);
+    write(REG_TIMER_INIT, 0);
+
+    let per_period = (elapsed as u64 * 1000 / (CALIBRATION_MS as u64 * hz as u64)).max(1) as u32;
+    write(REG_LVT_TIMER, LVT_TIMER_PERIODIC | TIMER_VECTOR as u32);
+    write(REG_TIMER_INIT, per_period);
+    per_period
+}
+
+/// Signals end of interrupt to the local APIC.
+pub fn eoi() {
+    write(REG_EOI, 0);
+}
+
+/// EOIs the highest in-service vector, if any. Returns false when nothing
+/// is in service (spurious interrupts never set an ISR bit).
+pub fn eoi_in_service() -> bool {
+    if !is_enabled() {
+        return false;
+    }
+    let in_service = (0..8).any(|i| read(REG_ISR_BASE + i * 0x10) != 0);
+    if in_service {
+        eoi();
+    }
+    in_service
+}
@@ -1,4 +1,11 @@
+pub mod port;
 pub mod pic;
 pub mod pit;
+pub mod lapic;
 pub mod idt;
-pub mod gdt;
\ No newline at end of file
+pub mod gdt;
+pub mod extable;
+pub mod usercopy;
+pub mod cet;
+pub mod spec_ctrl;
+pub mod hal;
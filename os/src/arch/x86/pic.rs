@@ -2,11 +2,19 @@
 //!
 //! Remap IRQ 0–15 to IDT vectors 32–47 (0x20–0x2F).
 //! Initially masks all IRQs except timer (IRQ0).
+//!
+//! With `pic=off` both chips are masked via `disable` and
+//! `notify_end_of_interrupt` becomes a shim that forwards to the LAPIC.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::x86::{lapic, port::PortRange};
+
+const MASTER_BASE: u16 = 0x20;
+const SLAVE_BASE: u16 = 0xA0;
 
-const MASTER_CMD: u16 = 0x20;
-const MASTER_DATA: u16 = 0x21;
-const SLAVE_CMD: u16 = 0xA0;
-const SLAVE_DATA: u16 = 0xA1;
+// Port offsets within each chip
+const CMD: u16 = 0;
+const DATA: u16 = 1;
 
 const ICW1_INIT: u8 = 0x11;
 const ICW4_8086: u8 = 0x01;
@@ -16,61 +24,173 @@ const MASTER_CASCADE: u8 = 0x04; // IR2 has slave
 const SLAVE_CASCADE: u8 = 0x02;  // connected to master's IR2
 const EOI: u8 = 0x20;
 
+/// Set once both PICs are masked in favour of the LAPIC
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
 /// IRQs handled by PIC
 pub const IRQ_TIMER: u8 = 0;
 pub const IRQ_KEYBOARD: u8 = 1;
-pub const IRQ_UNKNOWN: u8 = 0xFF; // for unexpected interrupts
 
-/// Write byte to port
-#[inline(always)]
-unsafe fn outb(port: u16, value: u8) {
-    core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nostack, preserves_flags));
-}
+/// IDT vectors of the IRQs above (master remapped to 0x20)
+pub const TIMER_VECTOR: u8 = MASTER_VECTOR + IRQ_TIMER;
+pub const KEYBOARD_VECTOR: u8 = MASTER_VECTOR + IRQ_KEYBOARD;
 
-/// Read byte from port
-#[inline(always)]
-unsafe fn inb(port: u16) -> u8 {
-    let value: u8;
-    core::arch::asm!("in al, dx", in("dx") port, out("al") value, options(nostack, preserves_flags));
-    value
-}
+/// Lowest-priority line of each chip; where spurious interrupts show up
+pub const IRQ_SPURIOUS_MASTER: u8 = 7;
+pub const IRQ_SPURIOUS_SLAVE: u8 = 15;
+
+/// OCW3: next read of the command port returns the In-Service Register
+const OCW3_READ_ISR: u8 = 0x0B;
+
+// Claimed in `init`
+static MASTER: PortRange = unsafe { PortRange::new(MASTER_BASE, 2) };
+static SLAVE: PortRange = unsafe { PortRange::new(SLAVE_BASE, 2) };
 
 /// Initialize PICs: remap IRQs, mask all except IRQ0 (timer).
 /// Safe to call only once at kernel startup.
 pub fn init() {
-    unsafe {
-        let mask_master = inb(MASTER_DATA);
-        let mask_slave = inb(SLAVE_DATA);
+    for (chip, name) in [(&MASTER, "pic1"), (&SLAVE, "pic2")] {
+        if let Err(e) = chip.claim(name, 0) {
+            crate::kernel::resource::log_conflict(name, e);
+        }
+    }
+
+    let mask_master = MASTER.read8(DATA);
+    let mask_slave = SLAVE.read8(DATA);
 
-        // Start initialization
-        outb(MASTER_CMD, ICW1_INIT);
-        outb(SLAVE_CMD, ICW1_INIT);
+    // Start initialization
+    MASTER.write8(CMD, ICW1_INIT);
+    SLAVE.write8(CMD, ICW1_INIT);
 
-        // Remap vectors
-        outb(MASTER_DATA, MASTER_VECTOR);
-        outb(SLAVE_DATA, SLAVE_VECTOR);
+    // Remap vectors
+    MASTER.write8(DATA, MASTER_VECTOR);
+    SLAVE.write8(DATA, SLAVE_VECTOR);
 
-        // Setup cascade
-        outb(MASTER_DATA, MASTER_CASCADE);
-        outb(SLAVE_DATA, SLAVE_CASCADE);
+    // Setup cascade
+    MASTER.write8(DATA, MASTER_CASCADE);
+    SLAVE.write8(DATA, SLAVE_CASCADE);
 
-        // 8086 mode
-        outb(MASTER_DATA, ICW4_8086);
-        outb(SLAVE_DATA, ICW4_8086);
+    // 8086 mode
+    MASTER.write8(DATA, ICW4_8086);
+    SLAVE.write8(DATA, ICW4_8086);
+
+    // Mask all IRQs except timer (IRQ0)
+    MASTER.write8(DATA, mask_master & !0x01);
+    SLAVE.write8(DATA, mask_slave);
+}
+
+/// Unmask one IRQ line (and the cascade for slave lines).
+/// Does nothing while the PICs are disabled.
+pub fn unmask(irq: u8) {
+    if is_disabled() {
+        return;
+    }
+    if irq >= 8 {
+        let mask = SLAVE.read8(DATA);
+        SLAVE.write8(DATA, mask & !(1 << (irq - 8)));
+        let mask = MASTER.read8(DATA);
+        MASTER.write8(DATA, mask & !(1 << 2));
+    } else {
+        let mask = MASTER.read8(DATA);
+        MASTER.write8(DATA, mask & !(1 << irq));
+    }
+}
+
+/// In-Service Registers of both chips (slave in the high byte).
+fn read_isr() -> u16 {
+    MASTER.write8(CMD, OCW3_READ_ISR);
+    SLAVE.write8(CMD, OCW3_READ_ISR);
+    (SLAVE.read8(CMD) as u16) << 8 | MASTER.read8(CMD) as u16
+}
+
+/// True if `irq` (7 or 15) was raised without its ISR bit set, i.e. the
+/// request went away before the CPU acknowledged it.
+pub fn is_spurious(irq: u8) -> bool {
+    debug_assert!(irq == IRQ_SPURIOUS_MASTER || irq == IRQ_SPURIOUS_SLAVE);
+    read_isr() & (1 << irq) == 0
+}
 
-        // Mask all IRQs except timer (IRQ0)
-        outb(MASTER_DATA, mask_master & !0x01);
-        outb(SLAVE_DATA, mask_slave);
+/// Acknowledge a spurious IRQ: nothing for IRQ7; for IRQ15 only the master,
+/// which did see a real request on its cascade line.
+pub fn notify_spurious(irq: u8) {
+    if irq >= 8 && !is_disabled() {
+        MASTER.write8(CMD, EOI);
     }
 }
 
+/// EOI whatever IRQ is currently in service, for vectors without a known
+/// IRQ number. Returns false if nothing was in service.
+pub fn eoi_in_service() -> bool {
+    if is_disabled() {
+        return lapic::eoi_in_service();
+    }
+    match in_service_irq(read_isr()) {
+        Some(irq) => {
+            notify_end_of_interrupt(irq);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Highest-priority IRQ in the combined ISR (slave in the high byte).
+///
+/// The master's cascade bit (IRQ2) stands for whatever the slave has in
+/// service, so it resolves to that slave IRQ: EOI-ing it then reaches both
+/// chips.
+fn in_service_irq(isr: u16) -> Option<u8> {
+    let (master, slave) = (isr as u8, (isr >> 8) as u8);
+    match master.trailing_zeros() {
+        2 | 8 if slave != 0 => Some(8 + slave.trailing_zeros() as u8),
+        8 => None,
+        irq => Some(irq as u8),
+    }
+}
+
+/// Mask every line on both PICs and route EOIs to the LAPIC.
+/// The LAPIC must already be enabled.
+pub fn disable() {
+    mask_all();
+    DISABLED.store(true, Ordering::SeqCst);
+}
+
+/// Mask every line on both PICs.
+pub fn mask_all() {
+    MASTER.write8(DATA, 0xFF);
+    SLAVE.write8(DATA, 0xFF);
+}
+
+/// True when running APIC-native (`pic=off`).
+pub fn is_disabled() -> bool {
+    DISABLED.load(Ordering::Relaxed)
+}
+
 /// Notify PIC that IRQ has been handled.
 /// Should be called at end of each IRQ handler.
 pub fn notify_end_of_interrupt(irq: u8) {
-    unsafe {
-        if irq >= 8 {
-            outb(SLAVE_CMD, EOI);
-        }
-        outb(MASTER_CMD, EOI);
+    if is_disabled() {
+        lapic::eoi();
+        return;
+    }
+    if irq >= 8 {
+        SLAVE.write8(CMD, EOI);
+    }
+    MASTER.write8(CMD, EOI);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_service_irq() {
+        assert_eq!(in_service_irq(0), None);
+        assert_eq!(in_service_irq(0b0000_0001), Some(0));
+        // IRQ 12 in service: slave bit 4 plus the master's cascade bit
+        assert_eq!(in_service_irq(0x10 << 8 | 0b0000_0100), Some(12));
+        // IRQ 1 nested above the cascade keeps priority
+        assert_eq!(in_service_irq(0x10 << 8 | 0b0000_0110), Some(1));
+        // Cascade bit alone (slave already acknowledged)
+        assert_eq!(in_service_irq(0b0000_0100), Some(2));
     }
 }
@@ -2,12 +2,30 @@
 //!
 //! Generates IRQ0 at a programmable frequency. Drives system tick.
 //! Default: 100 Hz (~10 ms per tick).
+//!
+//! Also provides `delay_ms`, a busy-wait that does not depend on interrupts
+//! (used for LAPIC timer calibration and by the PC speaker).
+
+use crate::arch::x86::port::PortRange;
+use crate::kernel::resource;
+
+// Port offsets within the PIT range
+const CH0_DATA: u16 = 0;
+const CMD: u16 = 3;
+
+// Claimed in `init`. The PIT range is a container for the speaker's
+// channel 2; port 0x61 is shared with the speaker.
+static PORTS: PortRange = unsafe { PortRange::new(0x40, 4) };
+static SYSTEM_CTRL: PortRange = unsafe { PortRange::new(0x61, 1) };
 
-const CH0_DATA: u16 = 0x40;
-const CMD: u16 = 0x43;
+/// Port 0x61 bit 4 toggles with DRAM refresh, every ~15.085 µs
+const REFRESH_TOGGLE: u8 = 0x10;
+
+/// Refresh bit toggles per millisecond (1 ms / 15.085 µs)
+const REFRESH_TOGGLES_PER_MS: u32 = 66;
 
 /// PIT input clock in Hz
-const PIT_BASE_HZ: u32 = 1_193_182;
+pub const PIT_BASE_HZ: u32 = 1_193_182;
 
 /// Target tick rate
 pub const TICK_HZ: u32 = 100;
@@ -15,15 +33,15 @@ pub const TICK_HZ: u32 = 100;
 /// Command: channel 0, lo/hi bytes, mode 3 (square wave), binary
 const CMD_CH0_SQUARE: u8 = 0x36;
 
-#[inline(always)]
-fn outb(port: u16, value: u8) {
-    unsafe {
-        core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nostack, preserves_flags));
-    }
-}
-
 /// Initialize PIT channel 0 to generate IRQ0 at `TICK_HZ`.
 pub fn init() {
+    if let Err(e) = PORTS.claim("pit", resource::CONTAINER) {
+        resource::log_conflict("pit", e);
+    }
+    if let Err(e) = SYSTEM_CTRL.claim("system control B", resource::SHARED) {
+        resource::log_conflict("system control B", e);
+    }
+
     let divisor = PIT_BASE_HZ / TICK_HZ;
     assert!(divisor > 0, "PIT divisor must be > 0");
 
@@ -31,7 +49,23 @@ pub fn init() {
     let divisor_hi = (divisor >> 8) as u8;
 
     // Program PIT
-    outb(CMD, CMD_CH0_SQUARE);
-    outb(CH0_DATA, divisor_lo);
-    outb(CH0_DATA, divisor_hi);
+    PORTS.write8(CMD, CMD_CH0_SQUARE);
+    PORTS.write8(CH0_DATA, divisor_lo);
+    PORTS.write8(CH0_DATA, divisor_hi);
+}
+
+/// Busy-waits for about `ms` milliseconds by counting refresh bit toggles.
+///
+/// Works with interrupts disabled and before the tick is running.
+pub fn delay_ms(ms: u32) {
+    let mut last = SYSTEM_CTRL.read8(0) & REFRESH_TOGGLE;
+    let mut toggles = ms.saturating_mul(REFRESH_TOGGLES_PER_MS);
+    while toggles > 0 {
+        let now = SYSTEM_CTRL.read8(0) & REFRESH_TOGGLE;
+        if now != last {
+            last = now;
+            toggles -= 1;
+        }
+        core::hint::spin_loop();
+    }
 }
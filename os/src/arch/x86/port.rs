@@ -0,0 +1,214 @@
+//! I/O port access.
+//!
+//! A `PortRange` is a window of consecutive ports belonging to one device.
+//! Accesses take an offset into the window, so a driver cannot reach ports
+//! outside of it. Ownership is recorded in the resource tree: a range is
+//! either obtained with `PortRange::request`, or declared as a static with
+//! `PortRange::new` and registered with `claim` at init.
+//!
+//! The actual `in`/`out` instructions sit behind `PortIo`, so register
+//! sequences can be exercised on the host against a mock backend.
+
+use crate::kernel::resource::{self, ResourceError, ResourceHandle, ResourceKind};
+
+/// Port access backend
+pub trait PortIo {
+    fn read8(&self, port: u16) -> u8;
+    fn write8(&self, port: u16, value: u8);
+    fn read16(&self, port: u16) -> u16;
+    fn write16(&self, port: u16, value: u16);
+    fn read32(&self, port: u16) -> u32;
+    fn write32(&self, port: u16, value: u32);
+}
+
+/// Real port I/O. Only constructed through `PortRange::new`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pio(());
+
+impl PortIo for Pio {
+    #[inline(always)]
+    fn read8(&self, port: u16) -> u8 {
+        let value: u8;
+        unsafe {
+            core::arch::asm!("in al, dx", in("dx") port, out("al") value, options(nostack, preserves_flags));
+        }
+        value
+    }
+
+    #[inline(always)]
+    fn write8(&self, port: u16, value: u8) {
+        unsafe {
+            core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nostack, preserves_flags));
+        }
+    }
+
+    #[inline(always)]
+    fn read16(&self, port: u16) -> u16 {
+        let value: u16;
+        unsafe {
+            core::arch::asm!("in ax, dx", in("dx") port, out("ax") value, options(nostack, preserves_flags));
+        }
+        value
+    }
+
+    #[inline(always)]
+    fn write16(&self, port: u16, value: u16) {
+        unsafe {
+            core::arch::asm!("out dx, ax", in("dx") port, in("ax") value, options(nostack, preserves_flags));
+        }
+    }
+
+    #[inline(always)]
+    fn read32(&self, port: u16) -> u32 {
+        let value: u32;
+        unsafe {
+            core::arch::asm!("in eax, dx", in("dx") port, out("eax") value, options(nostack, preserves_flags));
+        }
+        value
+    }
+
+    #[inline(always)]
+    fn write32(&self, port: u16, value: u32) {
+        unsafe {
+            core::arch::asm!("out dx, eax", in("dx") port, in("eax") value, options(nostack, preserves_flags));
+        }
+    }
+}
+
+/// Ports `base..base + len` of one device
+#[derive(Debug)]
+pub struct PortRange<B: PortIo = Pio> {
+    base: u16,
+    len: u16,
+    io: B,
+}
+
+impl PortRange<Pio> {
+    /// Describes a range without claiming it.
+    ///
+    /// # Safety
+    /// The ports must belong to the caller's device. Register them with
+    /// `claim` before the first access so conflicts are detected.
+    pub const unsafe fn new(base: u16, len: u16) -> Self {
+        Self { base, len, io: Pio(()) }
+    }
+
+    /// Claims `base..base + len` exclusively for `name` and returns it.
+    ///
+    /// # Errors
+    /// Whatever `resource::request` reports for the range.
+    pub fn request(base: u16, len: u16, name: &'static str) -> Result<Self, ResourceError> {
+        resource::request_ports(base, len, name)?;
+        Ok(Self { base, len, io: Pio(()) })
+    }
+}
+
+impl<B: PortIo> PortRange<B> {
+    /// Records ownership of the range in the resource tree.
+    ///
+    /// `flags` are `resource::CONTAINER`/`SHARED`.
+    pub fn claim(&self, name: &'static str, flags: u8) -> Result<ResourceHandle, ResourceError> {
+        resource::request(ResourceKind::Io, self.base as u64, self.len as u64, name, flags)
+    }
+
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    pub fn len(&self) -> u16 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Only the first port of a wide access is checked: devices such as
+    /// Bochs VBE take 16-bit accesses on their last port.
+    #[inline(always)]
+    fn port(&self, offset: u16) -> u16 {
+        debug_assert!(offset < self.len, "port offset outside range");
+        self.base + offset
+    }
+
+    #[inline(always)]
+    pub fn read8(&self, offset: u16) -> u8 {
+        self.io.read8(self.port(offset))
+    }
+
+    #[inline(always)]
+    pub fn write8(&self, offset: u16, value: u8) {
+        self.io.write8(self.port(offset), value)
+    }
+
+    #[inline(always)]
+    pub fn read16(&self, offset: u16) -> u16 {
+        self.io.read16(self.port(offset))
+    }
+
+    #[inline(always)]
+    pub fn write16(&self, offset: u16, value: u16) {
+        self.io.write16(self.port(offset), value)
+    }
+
+    #[inline(always)]
+    pub fn read32(&self, offset: u16) -> u32 {
+        self.io.read32(self.port(offset))
+    }
+
+    #[inline(always)]
+    pub fn write32(&self, offset: u16, value: u32) {
+        self.io.write32(self.port(offset), value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// 16 byte-wide registers at ports 0..16, little-endian for wide access
+    struct MockIo {
+        regs: [Cell<u8>; 16],
+    }
+
+    impl MockIo {
+        fn new() -> Self {
+            Self { regs: Default::default() }
+        }
+    }
+
+    impl PortIo for MockIo {
+        fn read8(&self, port: u16) -> u8 {
+            self.regs[port as usize].get()
+        }
+        fn write8(&self, port: u16, value: u8) {
+            self.regs[port as usize].set(value)
+        }
+        fn read16(&self, port: u16) -> u16 {
+            u16::from_le_bytes([self.read8(port), self.read8(port + 1)])
+        }
+        fn write16(&self, port: u16, value: u16) {
+            for (i, b) in value.to_le_bytes().into_iter().enumerate() {
+                self.write8(port + i as u16, b);
+            }
+        }
+        fn read32(&self, port: u16) -> u32 {
+            u32::from(self.read16(port)) | u32::from(self.read16(port + 2)) << 16
+        }
+        fn write32(&self, port: u16, value: u32) {
+            self.write16(port, value as u16);
+            self.write16(port + 2, (value >> 16) as u16);
+        }
+    }
+
+    #[test]
+    fn test_offsets_are_relative_to_base() {
+        let range = PortRange { base: 4, len: 8, io: MockIo::new() };
+        range.write8(1, 0xAB);
+        assert_eq!(range.io.regs[5].get(), 0xAB);
+        range.write32(4, 0x1122_3344);
+        assert_eq!(range.read16(4), 0x3344);
+        assert_eq!(range.read8(7), 0x11);
+    }
+}
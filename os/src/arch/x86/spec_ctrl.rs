@@ -0,0 +1,195 @@
+//! Speculative execution mitigations (Spectre v2 / SSB)
+//!
+//! Controlled by `mitigations=off|auto` on the command line (default auto):
+//! - IBRS: with enhanced IBRS (ARCH_CAPABILITIES.IBRS_ALL) it is set once
+//!   and stays on. Legacy IBRS would need toggling on every kernel entry,
+//!   which the kernel has no entry path for yet, so it is left off.
+//! - STIBP: set when supported, isolating the sibling hyperthread.
+//! - SSBD (speculative store bypass): set when supported and the CPU does
+//!   not report SSB_NO, through IA32_SPEC_CTRL or, on AMD guests without
+//!   it, VIRT_SPEC_CTRL. There are no tasks to opt in individually, so it
+//!   is on for the whole system.
+//! - IBPB: issued by `barrier_on_switch` whenever a different address space
+//!   is loaded, so one process cannot train branches for the next.
+//!
+//! The chosen state is available from `state()` and logged at boot.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use x86_64::registers::model_specific::Msr;
+use crate::serial;
+
+const IA32_SPEC_CTRL: u32 = 0x48;
+const IA32_PRED_CMD: u32 = 0x49;
+const IA32_ARCH_CAPABILITIES: u32 = 0x10A;
+const AMD_VIRT_SPEC_CTRL: u32 = 0xC001_011F;
+
+const SPEC_CTRL_IBRS: u64 = 1 << 0;
+const SPEC_CTRL_STIBP: u64 = 1 << 1;
+/// Same bit in IA32_SPEC_CTRL and VIRT_SPEC_CTRL
+const SPEC_CTRL_SSBD: u64 = 1 << 2;
+const PRED_CMD_IBPB: u64 = 1 << 0;
+const ARCH_CAP_IBRS_ALL: u64 = 1 << 1;
+const ARCH_CAP_SSB_NO: u64 = 1 << 4;
+
+// CPUID.(EAX=7,ECX=0):EDX
+const CPUID7_SPEC_CTRL: u32 = 1 << 26;
+const CPUID7_STIBP: u32 = 1 << 27;
+const CPUID7_ARCH_CAPABILITIES: u32 = 1 << 29;
+const CPUID7_SSBD: u32 = 1 << 31;
+// CPUID.80000008h:EBX (AMD)
+const AMD_IBPB: u32 = 1 << 12;
+const AMD_IBRS: u32 = 1 << 14;
+const AMD_STIBP: u32 = 1 << 15;
+const AMD_SSBD: u32 = 1 << 24;
+const AMD_VIRT_SSBD: u32 = 1 << 25;
+const AMD_SSB_NO: u32 = 1 << 26;
+
+// STATE bits
+const STATE_IBRS: u8 = 1 << 0;
+const STATE_STIBP: u8 = 1 << 1;
+const STATE_IBPB: u8 = 1 << 2;
+const STATE_SSBD: u8 = 1 << 3;
+const STATE_DISABLED: u8 = 1 << 7;
+
+static STATE: AtomicU8 = AtomicU8::new(0);
+
+/// What the CPU offers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpecCtrlSupport {
+    pub ibrs: bool,
+    pub enhanced_ibrs: bool,
+    pub stibp: bool,
+    pub ibpb: bool,
+    /// SSBD through IA32_SPEC_CTRL
+    pub ssbd: bool,
+    /// SSBD through AMD VIRT_SPEC_CTRL (hypervisor-provided)
+    pub virt_ssbd: bool,
+    /// Not affected by speculative store bypass
+    pub ssb_no: bool,
+}
+
+/// What is in effect
+#[derive(Debug, Clone, Copy)]
+pub struct MitigationState {
+    /// `mitigations=off` was given
+    pub disabled: bool,
+    pub ibrs: bool,
+    pub stibp: bool,
+    pub ssbd: bool,
+    /// IBPB on address space switches
+    pub ibpb_on_switch: bool,
+}
+
+pub fn support() -> SpecCtrlSupport {
+    use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+    let mut s = SpecCtrlSupport::default();
+    if __cpuid(0).eax >= 7 {
+        let edx = __cpuid_count(7, 0).edx;
+        s.ibrs = edx & CPUID7_SPEC_CTRL != 0;
+        s.ibpb = edx & CPUID7_SPEC_CTRL != 0;
+        s.stibp = edx & CPUID7_STIBP != 0;
+        s.ssbd = edx & CPUID7_SSBD != 0;
+        if edx & CPUID7_ARCH_CAPABILITIES != 0 {
+            let caps = unsafe { Msr::new(IA32_ARCH_CAPABILITIES).read() };
+            s.enhanced_ibrs = caps & ARCH_CAP_IBRS_ALL != 0;
+            s.ssb_no = caps & ARCH_CAP_SSB_NO != 0;
+        }
+    }
+    if __cpuid(0x8000_0000).eax >= 0x8000_0008 {
+        let ebx = __cpuid(0x8000_0008).ebx;
+        s.ibpb |= ebx & AMD_IBPB != 0;
+        s.ibrs |= ebx & AMD_IBRS != 0;
+        s.stibp |= ebx & AMD_STIBP != 0;
+        s.ssbd |= ebx & AMD_SSBD != 0;
+        s.virt_ssbd = ebx & AMD_VIRT_SSBD != 0;
+        s.ssb_no |= ebx & AMD_SSB_NO != 0;
+    }
+    s
+}
+
+pub fn state() -> MitigationState {
+    let bits = STATE.load(Ordering::Relaxed);
+    MitigationState {
+        disabled: bits & STATE_DISABLED != 0,
+        ibrs: bits & STATE_IBRS != 0,
+        stibp: bits & STATE_STIBP != 0,
+        ssbd: bits & STATE_SSBD != 0,
+        ibpb_on_switch: bits & STATE_IBPB != 0,
+    }
+}
+
+/// Applies the `mitigations=` policy. Call once at boot, after cmdline init.
+pub fn init() {
+    let support = support();
+    let bits = match crate::kernel::cmdline::get("mitigations").unwrap_or("auto") {
+        "off" => STATE_DISABLED,
+        "auto" => apply_auto(support),
+        other => {
+            crate::kernel::log::log_warn!("spec_ctrl: unknown mitigations={}, using auto", other);
+            apply_auto(support)
+        }
+    };
+    STATE.store(bits, Ordering::Relaxed);
+    log(support);
+}
+
+/// Enables everything safe to leave on permanently; returns STATE bits.
+fn apply_auto(support: SpecCtrlSupport) -> u8 {
+    let mut bits = 0u8;
+    let mut spec_ctrl = 0;
+    if support.enhanced_ibrs {
+        spec_ctrl |= SPEC_CTRL_IBRS;
+        bits |= STATE_IBRS;
+    }
+    if support.stibp {
+        spec_ctrl |= SPEC_CTRL_STIBP;
+        bits |= STATE_STIBP;
+    }
+    if !support.ssb_no && support.ssbd {
+        spec_ctrl |= SPEC_CTRL_SSBD;
+        bits |= STATE_SSBD;
+    } else if !support.ssb_no && support.virt_ssbd {
+        unsafe { Msr::new(AMD_VIRT_SPEC_CTRL).write(SPEC_CTRL_SSBD) };
+        bits |= STATE_SSBD;
+    }
+    if spec_ctrl != 0 {
+        unsafe { Msr::new(IA32_SPEC_CTRL).write(spec_ctrl) };
+    }
+    if support.ibpb {
+        bits |= STATE_IBPB;
+    }
+    bits
+}
+
+fn log(support: SpecCtrlSupport) {
+    let s = state();
+    serial::write_fmt(format_args!(
+        "spec_ctrl: cpu ibrs={} eibrs={} stibp={} ibpb={} ssbd={} virt_ssbd={} ssb_no={}; {}\n",
+        support.ibrs, support.enhanced_ibrs, support.stibp, support.ibpb,
+        support.ssbd, support.virt_ssbd, support.ssb_no,
+        if s.disabled { "mitigations off" } else { "auto" }
+    ));
+    if !s.disabled {
+        serial::write_fmt(format_args!(
+            "spec_ctrl: IBRS {}, STIBP {}, SSBD {}, IBPB on switch {}\n",
+            if s.ibrs { "on" } else { "off" },
+            if s.stibp { "on" } else { "off" },
+            if s.ssbd { "on" } else { "off" },
+            if s.ibpb_on_switch { "on" } else { "off" }
+        ));
+    }
+}
+
+/// Issues an indirect branch prediction barrier.
+pub fn ibpb() {
+    unsafe { Msr::new(IA32_PRED_CMD).write(PRED_CMD_IBPB) };
+}
+
+/// Called before loading a different address space into CR3.
+#[inline]
+pub fn barrier_on_switch() {
+    if STATE.load(Ordering::Relaxed) & STATE_IBPB != 0 {
+        ibpb();
+    }
+}
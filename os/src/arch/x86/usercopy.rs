@@ -0,0 +1,102 @@
+//! Fault-tolerant memory access primitives
+//!
+//! Copies to and from user memory and MMIO probes that report a fault as an
+//! error instead of halting. Each faulting instruction is registered in the
+//! exception table (see `extable`).
+//!
+//! These routines do NOT check that addresses belong to user space or are
+//! mapped with the right permissions; callers must validate ranges first.
+
+use core::arch::global_asm;
+
+// rdi = dst, rsi = src, rdx = len. Returns bytes NOT copied in rax.
+//
+// `rep movsb` faults with RIP on the instruction itself and RCX holding the
+// remaining count, so the fixup only has to return RCX.
+global_asm!(
+    ".global __raw_copy_bytes",
+    "__raw_copy_bytes:",
+    "    mov rcx, rdx",
+    ".Lraw_copy_insn:",
+    "    rep movsb",
+    "    xor eax, eax",
+    "    ret",
+    ".Lraw_copy_fixup:",
+    "    mov rax, rcx",
+    "    ret",
+    ".pushsection __ex_table, \"a\"",
+    ".balign 8",
+    ".quad .Lraw_copy_insn, .Lraw_copy_fixup",
+    ".popsection",
+);
+
+// rdi = address. Returns value in eax, rdx = 0 on success / 1 on fault.
+global_asm!(
+    ".global __raw_probe_read_u32",
+    "__raw_probe_read_u32:",
+    "    xor edx, edx",
+    ".Lprobe_u32_insn:",
+    "    mov eax, dword ptr [rdi]",
+    "    ret",
+    ".Lprobe_u32_fixup:",
+    "    xor eax, eax",
+    "    mov edx, 1",
+    "    ret",
+    ".pushsection __ex_table, \"a\"",
+    ".balign 8",
+    ".quad .Lprobe_u32_insn, .Lprobe_u32_fixup",
+    ".popsection",
+);
+
+/// Result of `__raw_probe_read_u32` (rax, rdx)
+#[repr(C)]
+struct ProbeResult {
+    value: u64,
+    faulted: u64,
+}
+
+extern "C" {
+    fn __raw_copy_bytes(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    fn __raw_probe_read_u32(addr: *const u32) -> ProbeResult;
+}
+
+/// A fault occurred while accessing memory; `remaining` bytes were not copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    pub remaining: usize,
+}
+
+/// Copies `dst.len()` bytes from `src` into `dst`, stopping at a fault.
+///
+/// # Safety
+/// `src..src + dst.len()` must be a user range already validated by the
+/// caller; it may be unmapped but must not alias kernel data.
+pub unsafe fn raw_copy_from_user(dst: &mut [u8], src: *const u8) -> Result<(), Fault> {
+    match __raw_copy_bytes(dst.as_mut_ptr(), src, dst.len()) {
+        0 => Ok(()),
+        remaining => Err(Fault { remaining }),
+    }
+}
+
+/// Copies `src` to `dst`, stopping at a fault.
+///
+/// # Safety
+/// `dst..dst + src.len()` must be a user range already validated by the
+/// caller; it may be unmapped but must not alias kernel data.
+pub unsafe fn raw_copy_to_user(dst: *mut u8, src: &[u8]) -> Result<(), Fault> {
+    match __raw_copy_bytes(dst, src.as_ptr(), src.len()) {
+        0 => Ok(()),
+        remaining => Err(Fault { remaining }),
+    }
+}
+
+/// Reads a 32-bit value, returning `None` if the access faults.
+///
+/// Used to probe MMIO registers of devices that may be absent.
+///
+/// # Safety
+/// `addr` must not have read side effects the caller did not intend.
+pub unsafe fn probe_read_u32(addr: *const u32) -> Option<u32> {
+    let result = __raw_probe_read_u32(addr);
+    (result.faulted == 0).then_some(result.value as u32)
+}
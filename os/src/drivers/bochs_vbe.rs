@@ -0,0 +1,172 @@
+//! Bochs/QEMU "dispi" VBE extensions (QEMU `-vga std`, Bochs VBE).
+//!
+//! Programs display resolution and depth through the index/data port pair
+//! at 0x1CE/0x1CF. The linear framebuffer is PCI BAR0 of the adapter, the
+//! same memory UEFI GOP hands to the bootloader, so a new mode reuses the
+//! existing framebuffer mapping and is re-published to `gfx`. Modes larger
+//! than that mapping are rejected until the kernel can map MMIO itself.
+//!
+//! Only 32 bpp is offered: 8 bpp is palettized and 15/16 bpp are not
+//! supported by the drawing code.
+
+use bootloader_api::info::{FrameBufferInfo, PixelFormat};
+use x86_64::instructions::interrupts;
+use crate::arch::x86::port::PortRange;
+use crate::serial;
+
+// Port offsets: register index, 16-bit data
+const INDEX: u16 = 0;
+const DATA: u16 = 1;
+
+// Claimed in `init`
+static PORTS: PortRange = unsafe { PortRange::new(0x01CE, 2) };
+
+const REG_ID: u16 = 0x0;
+const REG_XRES: u16 = 0x1;
+const REG_YRES: u16 = 0x2;
+const REG_BPP: u16 = 0x3;
+const REG_ENABLE: u16 = 0x4;
+const REG_VIRT_WIDTH: u16 = 0x6;
+const REG_X_OFFSET: u16 = 0x8;
+const REG_Y_OFFSET: u16 = 0x9;
+const REG_VIDEO_MEMORY_64K: u16 = 0xA;
+
+const ENABLE_DISABLED: u16 = 0x00;
+const ENABLE_ENABLED: u16 = 0x01;
+const ENABLE_GETCAPS: u16 = 0x02;
+const ENABLE_LFB: u16 = 0x40;
+
+/// Lowest and highest interface IDs understood by this driver
+const ID_MIN: u16 = 0xB0C0;
+const ID_MAX: u16 = 0xB0C5;
+
+/// First interface version reporting video memory size
+const ID_VIDEO_MEMORY: u16 = 0xB0C4;
+
+/// Only depth supported by the drawing code
+pub const BPP: u16 = 32;
+
+/// Mode setting errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VbeError {
+    /// No dispi interface at 0x1CE
+    NotPresent,
+    /// Requested depth other than 32 bpp
+    UnsupportedDepth,
+    /// Zero size or above the adapter's maximum resolution
+    InvalidResolution,
+    /// Mode needs more memory than the adapter or the current mapping has
+    ExceedsFramebuffer,
+}
+
+unsafe fn write_reg(reg: u16, value: u16) {
+    PORTS.write16(INDEX, reg);
+    PORTS.write16(DATA, value);
+}
+
+unsafe fn read_reg(reg: u16) -> u16 {
+    PORTS.write16(INDEX, reg);
+    PORTS.read16(DATA)
+}
+
+/// Returns the interface ID (0xB0C0..=0xB0C5) or `None` if absent.
+pub fn version() -> Option<u16> {
+    let id = interrupts::without_interrupts(|| unsafe { read_reg(REG_ID) });
+    (ID_MIN..=ID_MAX).contains(&id).then_some(id)
+}
+
+/// Returns true if a Bochs/QEMU dispi interface is present.
+pub fn is_present() -> bool {
+    version().is_some()
+}
+
+/// Returns the maximum (width, height, bpp) reported by the adapter.
+pub fn max_mode() -> Option<(u16, u16, u16)> {
+    version()?;
+    Some(interrupts::without_interrupts(|| unsafe {
+        let enable = read_reg(REG_ENABLE);
+        write_reg(REG_ENABLE, enable | ENABLE_GETCAPS);
+        let caps = (read_reg(REG_XRES), read_reg(REG_YRES), read_reg(REG_BPP));
+        write_reg(REG_ENABLE, enable);
+        caps
+    }))
+}
+
+/// Returns adapter video memory in bytes, if the interface reports it.
+pub fn video_memory() -> Option<usize> {
+    let id = version()?;
+    if id < ID_VIDEO_MEMORY {
+        return None;
+    }
+    let blocks = interrupts::without_interrupts(|| unsafe { read_reg(REG_VIDEO_MEMORY_64K) });
+    Some(blocks as usize * 64 * 1024)
+}
+
+/// Switches to `width` x `height` at `bpp` and re-publishes the framebuffer.
+///
+/// The framebuffer keeps its base address; only its layout changes.
+pub fn set_mode(width: u16, height: u16, bpp: u16) -> Result<FrameBufferInfo, VbeError> {
+    if !is_present() {
+        return Err(VbeError::NotPresent);
+    }
+    if bpp != BPP {
+        return Err(VbeError::UnsupportedDepth);
+    }
+
+    let (max_w, max_h, _) = max_mode().ok_or(VbeError::NotPresent)?;
+    if width == 0 || height == 0 || width > max_w || height > max_h {
+        return Err(VbeError::InvalidResolution);
+    }
+
+    let bytes_per_pixel = (bpp / 8) as usize;
+    let byte_len = width as usize * height as usize * bytes_per_pixel;
+    let mapped = crate::gfx::mapped_len().unwrap_or(0);
+    if byte_len > mapped || video_memory().is_some_and(|mem| byte_len > mem) {
+        return Err(VbeError::ExceedsFramebuffer);
+    }
+
+    interrupts::without_interrupts(|| unsafe {
+        write_reg(REG_ENABLE, ENABLE_DISABLED);
+        write_reg(REG_XRES, width);
+        write_reg(REG_YRES, height);
+        write_reg(REG_BPP, bpp);
+        write_reg(REG_VIRT_WIDTH, width);
+        write_reg(REG_X_OFFSET, 0);
+        write_reg(REG_Y_OFFSET, 0);
+        write_reg(REG_ENABLE, ENABLE_ENABLED | ENABLE_LFB);
+    });
+
+    // 32 bpp dispi pixels are 0x00RRGGBB little-endian: B, G, R, X in memory
+    let info = FrameBufferInfo {
+        byte_len,
+        width: width as usize,
+        height: height as usize,
+        pixel_format: PixelFormat::Bgr,
+        bytes_per_pixel,
+        stride: width as usize,
+    };
+
+    crate::gfx::set_mode_info(info).map_err(|_| VbeError::ExceedsFramebuffer)?;
+    Ok(info)
+}
+
+/// Probes the adapter and logs its capabilities.
+pub fn init() {
+    if let Err(e) = PORTS.claim("bochs_vbe", 0) {
+        crate::kernel::resource::log_conflict("bochs_vbe", e);
+        return;
+    }
+    let Some(id) = version() else {
+        serial::write_str("vbe: no Bochs/QEMU dispi interface\n");
+        return;
+    };
+    let (w, h, bpp) = max_mode().unwrap_or((0, 0, 0));
+    serial::write_fmt(format_args!(
+        "vbe: dispi {:#06x}, max {}x{}x{}, {} KiB video memory\n",
+        id,
+        w,
+        h,
+        bpp,
+        video_memory().unwrap_or(0) / 1024
+    ));
+}
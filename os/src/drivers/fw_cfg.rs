@@ -0,0 +1,199 @@
+//! QEMU firmware configuration (fw_cfg) interface.
+//!
+//! Lets the host hand data to the guest without rebuilding the disk image:
+//! `-fw_cfg name=opt/os/cmdline,string=...` or `-fw_cfg name=opt/os/test,file=...`.
+//!
+//! Uses the legacy I/O port interface: 16-bit selector at 0x510, byte-wide
+//! data at 0x511. Each selector write resets the data offset to 0. The DMA
+//! interface (0x514) is detected and reported but not used: everything read
+//! here (file directory, command line, test name) is a few KiB at most,
+//! read once at boot, where byte-wise port I/O costs nothing noticeable.
+//! DMA would need a physically contiguous descriptor and a buffer split at
+//! every page boundary (`AddressSpace::translate` per page), which only
+//! pays off for large blobs such as an initrd.
+
+use x86_64::instructions::interrupts;
+use crate::arch::x86::port::PortRange;
+use crate::serial;
+
+// Port offsets: 16-bit selector, byte-wide data
+const SELECTOR: u16 = 0;
+const DATA: u16 = 1;
+
+// Claimed in `init`
+static PORTS: PortRange = unsafe { PortRange::new(0x510, 2) };
+
+const KEY_SIGNATURE: u16 = 0x0000;
+const KEY_ID: u16 = 0x0001;
+const KEY_FILE_DIR: u16 = 0x0019;
+
+/// Feature bit in the ID item: DMA interface available
+const ID_DMA: u32 = 1 << 1;
+
+/// Maximum file name length in the directory (NUL-padded)
+pub const FILE_NAME_LEN: usize = 56;
+
+/// Host-provided extra kernel parameters
+pub const CMDLINE_FILE: &str = "opt/os/cmdline";
+
+/// Host-provided integration test scenario name
+pub const TEST_SCENARIO_FILE: &str = "opt/os/test";
+
+/// Entry of the fw_cfg file directory
+#[derive(Clone, Copy)]
+pub struct FwCfgFile {
+    /// Size of the item in bytes
+    pub size: u32,
+    /// Selector key used to read the item
+    pub select: u16,
+    name: [u8; FILE_NAME_LEN],
+}
+
+impl FwCfgFile {
+    /// Returns the file name (e.g. "etc/e820", "opt/os/cmdline").
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(FILE_NAME_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("<invalid>")
+    }
+}
+
+impl core::fmt::Debug for FwCfgFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FwCfgFile")
+            .field("name", &self.name())
+            .field("size", &self.size)
+            .field("select", &self.select)
+            .finish()
+    }
+}
+
+/// Selects `key` and reads `buf.len()` bytes from the start of the item.
+///
+/// # Safety
+/// Caller must hold exclusive access to the selector (interrupts disabled).
+unsafe fn select_and_read(key: u16, buf: &mut [u8]) {
+    PORTS.write16(SELECTOR, key);
+    for byte in buf.iter_mut() {
+        *byte = PORTS.read8(DATA);
+    }
+}
+
+/// Discards `count` bytes from the currently selected item.
+///
+/// # Safety
+/// Same as `select_and_read`; must follow a selector write.
+unsafe fn skip(count: usize) {
+    for _ in 0..count {
+        let _ = PORTS.read8(DATA);
+    }
+}
+
+/// Returns true if the fw_cfg signature ("QEMU") is present.
+pub fn is_present() -> bool {
+    let mut sig = [0u8; 4];
+    interrupts::without_interrupts(|| unsafe { select_and_read(KEY_SIGNATURE, &mut sig) });
+    &sig == b"QEMU"
+}
+
+/// Returns true if the host advertises the DMA interface.
+pub fn has_dma() -> bool {
+    let mut id = [0u8; 4];
+    interrupts::without_interrupts(|| unsafe { select_and_read(KEY_ID, &mut id) });
+    u32::from_le_bytes(id) & ID_DMA != 0
+}
+
+/// Calls `f` for every entry in the file directory.
+///
+/// Stops early if `f` returns false. The directory is big-endian.
+pub fn for_each_file(mut f: impl FnMut(&FwCfgFile) -> bool) {
+    if !is_present() {
+        return;
+    }
+
+    interrupts::without_interrupts(|| unsafe {
+        let mut count = [0u8; 4];
+        select_and_read(KEY_FILE_DIR, &mut count);
+
+        for _ in 0..u32::from_be_bytes(count) {
+            let mut raw = [0u8; 8 + FILE_NAME_LEN];
+            for byte in raw.iter_mut() {
+                *byte = PORTS.read8(DATA);
+            }
+
+            let mut file = FwCfgFile {
+                size: u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]),
+                select: u16::from_be_bytes([raw[4], raw[5]]),
+                name: [0; FILE_NAME_LEN],
+            };
+            file.name.copy_from_slice(&raw[8..]);
+
+            if !f(&file) {
+                break;
+            }
+        }
+    });
+}
+
+/// Looks up a file by exact name.
+pub fn find_file(name: &str) -> Option<FwCfgFile> {
+    let mut found = None;
+    for_each_file(|file| {
+        if file.name() == name {
+            found = Some(*file);
+            false
+        } else {
+            true
+        }
+    });
+    found
+}
+
+/// Reads up to `buf.len()` bytes of `file` starting at `offset`.
+///
+/// Returns the number of bytes copied (0 if `offset` is past the end).
+pub fn read_file(file: &FwCfgFile, offset: usize, buf: &mut [u8]) -> usize {
+    let size = file.size as usize;
+    if offset >= size {
+        return 0;
+    }
+    let len = buf.len().min(size - offset);
+
+    interrupts::without_interrupts(|| unsafe {
+        PORTS.write16(SELECTOR, file.select);
+        skip(offset);
+        for byte in buf[..len].iter_mut() {
+            *byte = PORTS.read8(DATA);
+        }
+    });
+    len
+}
+
+/// Reads the named file into `buf`. Returns `None` if it does not exist.
+pub fn read_file_by_name(name: &str, buf: &mut [u8]) -> Option<usize> {
+    find_file(name).map(|file| read_file(&file, 0, buf))
+}
+
+/// Probes fw_cfg and logs the file directory.
+pub fn init() {
+    if let Err(e) = PORTS.claim("fw_cfg", 0) {
+        crate::kernel::resource::log_conflict("fw_cfg", e);
+        return;
+    }
+    if !is_present() {
+        serial::write_str("fw_cfg: not present\n");
+        return;
+    }
+
+    serial::write_str("fw_cfg: present, DMA ");
+    serial::write_str(if has_dma() { "supported (unused)\n" } else { "not supported\n" });
+
+    for_each_file(|file| {
+        serial::write_fmt(format_args!(
+            "fw_cfg:   {:#06x} {:>8} {}\n",
+            file.select,
+            file.size,
+            file.name()
+        ));
+        true
+    });
+}
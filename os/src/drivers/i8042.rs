@@ -0,0 +1,305 @@
+//! i8042 PS/2 controller.
+//!
+//! Brings the controller into a known state instead of trusting firmware:
+//! 1. disable both ports and flush the output buffer
+//! 2. mask port IRQs and translation while testing
+//! 3. controller self-test (0xAA), dual-channel check, port tests
+//! 4. reset the device on each working port (0xFF) to see if one is plugged in
+//! 5. enable populated ports, port 1 IRQ and set 1 translation
+//!
+//! Device drivers (keyboard, mouse) talk to their port through `write_port`
+//! and `read_data`. PS/2 has no plug interrupt, so a device attached after
+//! boot is picked up only by an explicit `rescan`.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use crate::arch::x86::port::PortRange;
+use crate::serial;
+
+// Claimed in `init`. 0x61-0x63 between them belong to other devices.
+static DATA_PORT: PortRange = unsafe { PortRange::new(0x60, 1) };
+/// Status on read, command on write
+static COMMAND_PORT: PortRange = unsafe { PortRange::new(0x64, 1) };
+
+const STATUS_OUTPUT_FULL: u8 = 0x01;
+const STATUS_INPUT_FULL: u8 = 0x02;
+
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_PORT2: u8 = 0xA7;
+const CMD_ENABLE_PORT2: u8 = 0xA8;
+const CMD_TEST_PORT2: u8 = 0xA9;
+const CMD_SELF_TEST: u8 = 0xAA;
+const CMD_TEST_PORT1: u8 = 0xAB;
+const CMD_DISABLE_PORT1: u8 = 0xAD;
+const CMD_ENABLE_PORT1: u8 = 0xAE;
+const CMD_WRITE_PORT2: u8 = 0xD4;
+
+const CONFIG_PORT1_IRQ: u8 = 1 << 0;
+const CONFIG_PORT2_IRQ: u8 = 1 << 1;
+const CONFIG_PORT2_CLOCK_OFF: u8 = 1 << 5;
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+
+const SELF_TEST_OK: u8 = 0x55;
+const PORT_TEST_OK: u8 = 0x00;
+
+const DEV_RESET: u8 = 0xFF;
+const DEV_ACK: u8 = 0xFA;
+const DEV_SELF_TEST_OK: u8 = 0xAA;
+
+/// Status polls for ordinary transfers (~100 ms of port reads)
+pub const TIMEOUT_SPINS: u32 = 100_000;
+
+/// Status polls for a device reset, which takes up to several hundred ms
+const RESET_TIMEOUT_SPINS: u32 = 2_000_000;
+
+// STATE bits
+const STATE_PRESENT: u8 = 1 << 0;
+const STATE_DUAL: u8 = 1 << 1;
+const STATE_PORT1_OK: u8 = 1 << 2;
+const STATE_PORT2_OK: u8 = 1 << 3;
+const STATE_PORT1_DEVICE: u8 = 1 << 4;
+const STATE_PORT2_DEVICE: u8 = 1 << 5;
+
+static STATE: AtomicU8 = AtomicU8::new(0);
+
+/// Controller port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    /// Keyboard port (IRQ1)
+    First,
+    /// Auxiliary/mouse port (IRQ12)
+    Second,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I8042Error {
+    /// Controller did not accept or produce a byte in time
+    Timeout,
+    /// Self-test returned something other than 0x55
+    SelfTestFailed(u8),
+}
+
+fn status() -> u8 {
+    COMMAND_PORT.read8(0)
+}
+
+/// True if a byte is waiting in the output buffer.
+pub fn output_full() -> bool {
+    status() & STATUS_OUTPUT_FULL != 0
+}
+
+fn wait_input_empty(spins: u32) -> Result<(), I8042Error> {
+    for _ in 0..spins {
+        if status() & STATUS_INPUT_FULL == 0 {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+    Err(I8042Error::Timeout)
+}
+
+/// Waits up to `spins` status polls for a byte and reads it.
+pub fn read_data_timeout(spins: u32) -> Result<u8, I8042Error> {
+    for _ in 0..spins {
+        if output_full() {
+            return Ok(DATA_PORT.read8(0));
+        }
+        core::hint::spin_loop();
+    }
+    Err(I8042Error::Timeout)
+}
+
+/// Reads one byte from the output buffer, waiting a bounded time.
+pub fn read_data() -> Result<u8, I8042Error> {
+    read_data_timeout(TIMEOUT_SPINS)
+}
+
+/// Reads a byte without waiting, if one is available.
+pub fn try_read_data() -> Option<u8> {
+    output_full().then(|| DATA_PORT.read8(0))
+}
+
+/// Discards everything in the output buffer.
+pub fn flush() {
+    while try_read_data().is_some() {}
+}
+
+fn command(cmd: u8) -> Result<(), I8042Error> {
+    wait_input_empty(TIMEOUT_SPINS)?;
+    COMMAND_PORT.write8(0, cmd);
+    Ok(())
+}
+
+fn command_read(cmd: u8) -> Result<u8, I8042Error> {
+    command(cmd)?;
+    read_data()
+}
+
+fn write_config(config: u8) -> Result<(), I8042Error> {
+    command(CMD_WRITE_CONFIG)?;
+    wait_input_empty(TIMEOUT_SPINS)?;
+    DATA_PORT.write8(0, config);
+    Ok(())
+}
+
+/// Sends one byte to the device on `port`.
+pub fn write_port(port: Port, byte: u8) -> Result<(), I8042Error> {
+    if port == Port::Second {
+        command(CMD_WRITE_PORT2)?;
+    }
+    wait_input_empty(TIMEOUT_SPINS)?;
+    DATA_PORT.write8(0, byte);
+    Ok(())
+}
+
+pub fn is_present() -> bool {
+    STATE.load(Ordering::Relaxed) & STATE_PRESENT != 0
+}
+
+/// True if the controller has a second (mouse) channel.
+pub fn is_dual_channel() -> bool {
+    STATE.load(Ordering::Relaxed) & STATE_DUAL != 0
+}
+
+/// True if a device answered a reset on `port`.
+pub fn port_populated(port: Port) -> bool {
+    let bit = match port {
+        Port::First => STATE_PORT1_DEVICE,
+        Port::Second => STATE_PORT2_DEVICE,
+    };
+    STATE.load(Ordering::Relaxed) & bit != 0
+}
+
+/// Resets the device on `port`; true if it completed its self-test.
+///
+/// Run with the port's IRQ masked. Some mice append their ID byte after
+/// 0xAA; it is flushed.
+fn probe_device(port: Port) -> bool {
+    if write_port(port, DEV_RESET).is_err() {
+        return false;
+    }
+    let ok = read_data_timeout(RESET_TIMEOUT_SPINS) == Ok(DEV_ACK)
+        && read_data_timeout(RESET_TIMEOUT_SPINS) == Ok(DEV_SELF_TEST_OK);
+    flush();
+    ok
+}
+
+fn init_controller() -> Result<(), I8042Error> {
+    command(CMD_DISABLE_PORT1)?;
+    command(CMD_DISABLE_PORT2)?;
+    flush();
+
+    let mut config = command_read(CMD_READ_CONFIG)?;
+    config &= !(CONFIG_PORT1_IRQ | CONFIG_PORT2_IRQ | CONFIG_TRANSLATION);
+    write_config(config)?;
+
+    let result = command_read(CMD_SELF_TEST)?;
+    if result != SELF_TEST_OK {
+        return Err(I8042Error::SelfTestFailed(result));
+    }
+    // Self-test may reset the controller
+    write_config(config)?;
+
+    let mut state = STATE_PRESENT;
+
+    // A second channel un-gates its clock when enabled
+    command(CMD_ENABLE_PORT2)?;
+    if command_read(CMD_READ_CONFIG)? & CONFIG_PORT2_CLOCK_OFF == 0 {
+        state |= STATE_DUAL;
+        command(CMD_DISABLE_PORT2)?;
+    }
+
+    if command_read(CMD_TEST_PORT1)? == PORT_TEST_OK {
+        state |= STATE_PORT1_OK;
+    }
+    if state & STATE_DUAL != 0 && command_read(CMD_TEST_PORT2)? == PORT_TEST_OK {
+        state |= STATE_PORT2_OK;
+    }
+    STATE.store(state, Ordering::Relaxed);
+
+    rescan()?;
+    Ok(())
+}
+
+/// Re-detects devices on working ports and re-enables the populated ones.
+///
+/// Port 1 gets its IRQ and set 1 translation; port 2 stays without IRQ
+/// until a mouse driver exists.
+pub fn rescan() -> Result<(), I8042Error> {
+    let mut state = STATE.load(Ordering::Relaxed);
+    if state & STATE_PRESENT == 0 {
+        return Ok(());
+    }
+    state &= !(STATE_PORT1_DEVICE | STATE_PORT2_DEVICE);
+
+    let mut config = command_read(CMD_READ_CONFIG)?;
+    config &= !(CONFIG_PORT1_IRQ | CONFIG_PORT2_IRQ);
+    write_config(config)?;
+
+    if state & STATE_PORT1_OK != 0 {
+        command(CMD_ENABLE_PORT1)?;
+        if probe_device(Port::First) {
+            state |= STATE_PORT1_DEVICE;
+            config |= CONFIG_PORT1_IRQ | CONFIG_TRANSLATION;
+        } else {
+            command(CMD_DISABLE_PORT1)?;
+        }
+    }
+    if state & STATE_PORT2_OK != 0 {
+        command(CMD_ENABLE_PORT2)?;
+        if probe_device(Port::Second) {
+            state |= STATE_PORT2_DEVICE;
+        } else {
+            command(CMD_DISABLE_PORT2)?;
+        }
+    }
+
+    STATE.store(state, Ordering::Relaxed);
+    write_config(config)
+}
+
+/// Disables both ports and their IRQs so no device input arrives while
+/// the machine goes down.
+pub fn shutdown() {
+    if !is_present() {
+        return;
+    }
+    let _ = command(CMD_DISABLE_PORT1).and_then(|_| command(CMD_DISABLE_PORT2));
+    flush();
+    if let Ok(config) = command_read(CMD_READ_CONFIG) {
+        let _ = write_config(config & !(CONFIG_PORT1_IRQ | CONFIG_PORT2_IRQ));
+    }
+    STATE.store(0, Ordering::Relaxed);
+}
+
+/// Error recovery: re-runs the controller bring-up. On failure the
+/// controller is marked absent.
+pub fn reset() -> bool {
+    if init_controller().is_ok() {
+        return true;
+    }
+    STATE.store(0, Ordering::Relaxed);
+    false
+}
+
+/// Initializes the controller and logs what is attached.
+pub fn init() {
+    if let Err(e) = DATA_PORT
+        .claim("i8042 data", 0)
+        .and_then(|_| COMMAND_PORT.claim("i8042 command", 0))
+    {
+        crate::kernel::resource::log_conflict("i8042", e);
+        return;
+    }
+    if let Err(e) = init_controller() {
+        STATE.store(0, Ordering::Relaxed);
+        serial::write_fmt(format_args!("i8042: not usable ({:?})\n", e));
+        return;
+    }
+    serial::write_fmt(format_args!(
+        "i8042: {} channel, port 1 {}, port 2 {}\n",
+        if is_dual_channel() { "dual" } else { "single" },
+        if port_populated(Port::First) { "populated" } else { "empty" },
+        if port_populated(Port::Second) { "populated" } else { "empty" }
+    ));
+}
@@ -0,0 +1,209 @@
+//! Device drivers
+//!
+//! Platform devices reached through fixed I/O ports. Interrupt controller
+//! and timer code stays in `arch::x86`; everything here is optional for
+//! the kernel to boot.
+//!
+//! # Boot probing
+//! Drivers listed in `DRIVERS` are probed by `init_all`, which honors two
+//! command line options:
+//! - `blacklist=a,b` skips the named drivers
+//! - `probe_order=b,a` probes the named drivers first, in that order, then
+//!   the rest in table order
+//!
+//! Each probe is logged before it starts, so the last line on the serial
+//! console names the driver that hung. fw_cfg is not in the table: it
+//! supplies the command line and is set up before it.
+//!
+//! # Shutdown
+//! `shutdown_all` runs the `shutdown` hooks of probed drivers in reverse
+//! probe order, so a device is quiesced before anything it depends on.
+//!
+//! # Error recovery
+//! A driver that sees its device misbehave (timeouts, garbage responses)
+//! calls `report_error`. The device's `reset` hook is tried up to
+//! `MAX_RESET_ATTEMPTS` times; if none succeeds, if there is no hook, or if
+//! the device has already been recovered `MAX_RECOVERIES` times, it is
+//! taken offline: its `shutdown` hook runs and `is_online` turns false for
+//! good. Every step is logged.
+
+pub mod bochs_vbe;
+pub mod fw_cfg;
+pub mod i8042;
+pub mod ps2_keyboard;
+pub mod speaker;
+
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use crate::kernel::cmdline;
+use crate::kernel::log::{log_error, log_info, log_warn};
+use crate::serial;
+use crate::sync::RwLock;
+
+/// Resets tried for one reported error
+const MAX_RESET_ATTEMPTS: u32 = 3;
+
+/// Successful recoveries before a flapping device is given up on
+const MAX_RECOVERIES: u8 = 5;
+
+/// A driver probed at boot
+pub struct Driver {
+    /// Name used by `blacklist=` and `probe_order=`
+    pub name: &'static str,
+    /// Detects and initializes the device; logs its own outcome
+    pub init: fn(),
+    /// Stops the device before power-off or when it goes offline; must
+    /// cope with a failed init
+    pub shutdown: Option<fn()>,
+    /// Brings the device back to a working state after an error; returns
+    /// true on success
+    pub reset: Option<fn() -> bool>,
+}
+
+/// Indices into `DRIVERS` in the order they were probed
+struct ProbeLog {
+    order: [u8; 64],
+    len: usize,
+}
+
+static PROBED: RwLock<ProbeLog> = RwLock::new(ProbeLog { order: [0; 64], len: 0 });
+
+/// Bit per `DRIVERS` index: taken offline after unrecoverable errors
+static OFFLINE: AtomicU64 = AtomicU64::new(0);
+
+/// Successful recoveries per `DRIVERS` index
+static RECOVERIES: [AtomicU8; 64] = [const { AtomicU8::new(0) }; 64];
+
+/// Boot-probed drivers in default probe order
+pub static DRIVERS: &[Driver] = &[
+    Driver { name: "bochs_vbe", init: bochs_vbe::init, shutdown: None, reset: None },
+    Driver { name: "speaker", init: speaker::init, shutdown: Some(speaker::tone_off), reset: None },
+    Driver { name: "i8042", init: i8042::init, shutdown: Some(i8042::shutdown), reset: Some(i8042::reset) },
+    // Needs i8042 to have found a keyboard
+    Driver {
+        name: "ps2_keyboard",
+        init: ps2_keyboard::init,
+        shutdown: Some(ps2_keyboard::shutdown),
+        reset: Some(ps2_keyboard::reset),
+    },
+];
+
+/// True if the comma-separated `list` contains `name`.
+fn list_contains(list: &str, name: &str) -> bool {
+    list.split(',').any(|item| item.trim() == name)
+}
+
+/// Probes every registered driver, honoring `blacklist=` and `probe_order=`.
+pub fn init_all() {
+    let blacklist = cmdline::get("blacklist").unwrap_or("");
+    let order = cmdline::get("probe_order").unwrap_or("");
+    let mut probed = 0u64;
+
+    let mut probe = |index: usize| {
+        if probed & (1 << index) != 0 {
+            return;
+        }
+        probed |= 1 << index;
+
+        let driver = &DRIVERS[index];
+        if list_contains(blacklist, driver.name) {
+            serial::write_fmt(format_args!("drivers: {} blacklisted\n", driver.name));
+            return;
+        }
+        serial::write_fmt(format_args!("drivers: probing {}\n", driver.name));
+        (driver.init)();
+
+        let mut log = PROBED.write();
+        let len = log.len;
+        log.order[len] = index as u8;
+        log.len += 1;
+    };
+
+    for name in order.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        match DRIVERS.iter().position(|d| d.name == name) {
+            Some(index) => probe(index),
+            None => serial::write_fmt(format_args!("drivers: probe_order: unknown driver {}\n", name)),
+        }
+    }
+    for index in 0..DRIVERS.len() {
+        probe(index);
+    }
+}
+
+/// Runs the shutdown hooks of all probed drivers, last probed first.
+pub fn shutdown_all() {
+    let log = PROBED.read_irqsave();
+    for &index in log.order[..log.len].iter().rev() {
+        let driver = &DRIVERS[index as usize];
+        if let Some(shutdown) = driver.shutdown {
+            serial::write_fmt(format_args!("drivers: stopping {}\n", driver.name));
+            shutdown();
+        }
+    }
+}
+
+/// False once `name` has been taken offline by `report_error`.
+pub fn is_online(name: &str) -> bool {
+    DRIVERS
+        .iter()
+        .position(|d| d.name == name)
+        .is_some_and(|index| OFFLINE.load(Ordering::Relaxed) & (1 << index) == 0)
+}
+
+/// Takes driver `index` offline and stops its device.
+fn take_offline(index: usize, reason: &str) {
+    let driver = &DRIVERS[index];
+    OFFLINE.fetch_or(1 << index, Ordering::Relaxed);
+    log_error!("drivers: {} offline ({})", driver.name, reason);
+    if let Some(shutdown) = driver.shutdown {
+        shutdown();
+    }
+}
+
+/// Reports a device error from driver `name` and runs recovery.
+///
+/// Returns true if the device works again and the caller may retry the
+/// operation, false if it is (now) offline. May be called from IRQ
+/// context; resets are bounded by the drivers' own timeouts.
+pub fn report_error(name: &str, error: &dyn Debug) -> bool {
+    let Some(index) = DRIVERS.iter().position(|d| d.name == name) else {
+        log_warn!("drivers: error from unknown driver {} ({:?})", name, error);
+        return false;
+    };
+    if OFFLINE.load(Ordering::Relaxed) & (1 << index) != 0 {
+        return false;
+    }
+    let driver = &DRIVERS[index];
+    log_warn!("drivers: {}: {:?}", driver.name, error);
+
+    let Some(reset) = driver.reset else {
+        take_offline(index, "no reset hook");
+        return false;
+    };
+    if RECOVERIES[index].load(Ordering::Relaxed) >= MAX_RECOVERIES {
+        take_offline(index, "too many errors");
+        return false;
+    }
+    for attempt in 1..=MAX_RESET_ATTEMPTS {
+        if reset() {
+            RECOVERIES[index].fetch_add(1, Ordering::Relaxed);
+            log_info!("drivers: {} recovered after {} reset(s)", driver.name, attempt);
+            return true;
+        }
+    }
+    take_offline(index, "reset failed");
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_contains() {
+        assert!(list_contains("ps2_keyboard,ata", "ata"));
+        assert!(list_contains("ps2_keyboard, ata", "ata"));
+        assert!(!list_contains("ps2_keyboard,ata", "ps2"));
+        assert!(!list_contains("", "ata"));
+    }
+}
@@ -0,0 +1,237 @@
+//! PS/2 keyboard on i8042 port 1 (scancode set 1, translated by the
+//! controller).
+//!
+//! Tracks modifier and lock state from IRQ1 and keeps the Caps/Num/Scroll
+//! lock LEDs in sync with it. Requires `i8042::init` to have found a device
+//! on port 1.
+//!
+//! # Commands
+//! Device commands (0xED set LEDs, 0xF3 typematic) are answered with ACK
+//! (0xFA) or RESEND (0xFE). A RESEND is retried up to `MAX_RETRIES` times;
+//! every wait is bounded by the controller timeout, so a dead or flaky
+//! keyboard produces `KbdError::Timeout` instead of a hang.
+//!
+//! IRQ1 only records the new lock state. `poll` sends it from the kernel
+//! loop with interrupts disabled, so IRQ1 cannot take the ACK byte, and a
+//! failure goes through `drivers::report_error` (and possibly `reset`)
+//! outside interrupt context.
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use x86_64::instructions::interrupts;
+use crate::arch::x86::pic;
+use crate::drivers::i8042::{self, I8042Error, Port};
+use crate::kernel::log::log_debug;
+use crate::serial;
+
+const CMD_SET_LEDS: u8 = 0xED;
+const CMD_SET_TYPEMATIC: u8 = 0xF3;
+
+const RESP_ACK: u8 = 0xFA;
+const RESP_RESEND: u8 = 0xFE;
+
+/// Attempts per command when the keyboard asks for a resend
+const MAX_RETRIES: u32 = 3;
+
+/// LED bits of the 0xED argument
+pub const LED_SCROLL_LOCK: u8 = 1 << 0;
+pub const LED_NUM_LOCK: u8 = 1 << 1;
+pub const LED_CAPS_LOCK: u8 = 1 << 2;
+
+/// Modifier bits in `modifiers()`
+pub const MOD_SHIFT: u8 = 1 << 0;
+pub const MOD_CTRL: u8 = 1 << 1;
+pub const MOD_ALT: u8 = 1 << 2;
+
+/// Boot typematic setting: 10.9 characters/s after 500 ms
+pub const DEFAULT_TYPEMATIC_RATE: u8 = 0x0B;
+pub const DEFAULT_TYPEMATIC_DELAY: u8 = 1;
+
+// Set 1 make codes
+const SC_LSHIFT: u8 = 0x2A;
+const SC_RSHIFT: u8 = 0x36;
+const SC_CTRL: u8 = 0x1D;
+const SC_ALT: u8 = 0x38;
+const SC_CAPS_LOCK: u8 = 0x3A;
+const SC_NUM_LOCK: u8 = 0x45;
+const SC_SCROLL_LOCK: u8 = 0x46;
+const SC_EXTENDED: u8 = 0xE0;
+const SC_RELEASE: u8 = 0x80;
+
+static PRESENT: AtomicBool = AtomicBool::new(false);
+static MODIFIERS: AtomicU8 = AtomicU8::new(0);
+/// LED state last acknowledged by the keyboard
+static LEDS: AtomicU8 = AtomicU8::new(0);
+/// Lock state toggled from IRQ1; `poll` brings `LEDS` in line with it
+static LOCKS: AtomicU8 = AtomicU8::new(0);
+static LEDS_DIRTY: AtomicBool = AtomicBool::new(false);
+/// Previous byte was the 0xE0 prefix
+static EXTENDED: AtomicBool = AtomicBool::new(false);
+
+/// Keyboard command failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KbdError {
+    /// Controller or keyboard did not respond in time
+    Timeout,
+    /// Keyboard kept asking for a resend
+    TooManyResends,
+    /// Response other than ACK/RESEND
+    Unexpected(u8),
+}
+
+impl From<I8042Error> for KbdError {
+    fn from(_: I8042Error) -> Self {
+        KbdError::Timeout
+    }
+}
+
+/// Writes one byte to the keyboard and waits for its ACK, resending on 0xFE.
+fn send_byte(byte: u8) -> Result<(), KbdError> {
+    for _ in 0..MAX_RETRIES {
+        i8042::write_port(Port::First, byte)?;
+        match i8042::read_data()? {
+            RESP_ACK => return Ok(()),
+            RESP_RESEND => continue,
+            other => return Err(KbdError::Unexpected(other)),
+        }
+    }
+    Err(KbdError::TooManyResends)
+}
+
+/// Sends a command with its argument byte.
+fn send_command(cmd: u8, arg: u8) -> Result<(), KbdError> {
+    send_byte(cmd)?;
+    send_byte(arg)
+}
+
+/// Sets the lock LEDs (`LED_*` bits).
+pub fn set_leds(leds: u8) -> Result<(), KbdError> {
+    send_command(CMD_SET_LEDS, leds & 0x07)?;
+    LEDS.store(leds & 0x07, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Sets key repeat: `rate` 0 (30/s) ..= 31 (2/s), `delay` 0..=3 (250-1000 ms).
+pub fn set_typematic(rate: u8, delay: u8) -> Result<(), KbdError> {
+    send_command(CMD_SET_TYPEMATIC, (delay & 0x03) << 5 | (rate & 0x1F))
+}
+
+/// Current lock LED state (`LED_*` bits).
+pub fn leds() -> u8 {
+    LEDS.load(Ordering::Relaxed)
+}
+
+/// Current lock key state (`LED_*` bits); may be ahead of `leds()`.
+pub fn locks() -> u8 {
+    LOCKS.load(Ordering::Relaxed)
+}
+
+/// Currently held modifiers (`MOD_*` bits).
+pub fn modifiers() -> u8 {
+    MODIFIERS.load(Ordering::Relaxed)
+}
+
+pub fn is_present() -> bool {
+    PRESENT.load(Ordering::Relaxed)
+}
+
+/// Probes the keyboard, sets defaults and unmasks IRQ1.
+pub fn init() {
+    if !i8042::port_populated(Port::First) {
+        serial::write_str("ps2_keyboard: no keyboard on i8042 port 1\n");
+        return;
+    }
+    i8042::flush();
+
+    if let Err(e) = set_leds(0) {
+        serial::write_fmt(format_args!("ps2_keyboard: no response ({:?})\n", e));
+        return;
+    }
+    if let Err(e) = set_typematic(DEFAULT_TYPEMATIC_RATE, DEFAULT_TYPEMATIC_DELAY) {
+        serial::write_fmt(format_args!("ps2_keyboard: typematic not set ({:?})\n", e));
+    }
+
+    PRESENT.store(true, Ordering::Relaxed);
+    pic::unmask(pic::IRQ_KEYBOARD);
+    serial::write_str("ps2_keyboard: ready\n");
+}
+
+/// Error recovery: restores the LED and typematic settings, which a
+/// keyboard that reset itself has lost.
+pub fn reset() -> bool {
+    interrupts::without_interrupts(|| {
+        i8042::flush();
+        set_leds(locks()).is_ok() && set_typematic(DEFAULT_TYPEMATIC_RATE, DEFAULT_TYPEMATIC_DELAY).is_ok()
+    })
+}
+
+/// Stops handling keyboard input; IRQ1 bytes are drained and dropped.
+pub fn shutdown() {
+    PRESENT.store(false, Ordering::Relaxed);
+}
+
+/// Updates modifier/lock state for one scancode byte.
+fn process_scancode(byte: u8) {
+    if byte == SC_EXTENDED {
+        EXTENDED.store(true, Ordering::Relaxed);
+        return;
+    }
+    let extended = EXTENDED.swap(false, Ordering::Relaxed);
+    let released = byte & SC_RELEASE != 0;
+    let code = byte & !SC_RELEASE;
+
+    let modifier = match code {
+        SC_LSHIFT | SC_RSHIFT if !extended => MOD_SHIFT,
+        SC_CTRL => MOD_CTRL,
+        SC_ALT => MOD_ALT,
+        _ => 0,
+    };
+    if modifier != 0 {
+        if released {
+            MODIFIERS.fetch_and(!modifier, Ordering::Relaxed);
+        } else {
+            MODIFIERS.fetch_or(modifier, Ordering::Relaxed);
+        }
+        return;
+    }
+
+    if released || extended {
+        return;
+    }
+    let led = match code {
+        SC_CAPS_LOCK => LED_CAPS_LOCK,
+        SC_NUM_LOCK => LED_NUM_LOCK,
+        SC_SCROLL_LOCK => LED_SCROLL_LOCK,
+        _ => {
+            log_debug!("kbd: {:#04x}", code);
+            return;
+        }
+    };
+    LOCKS.fetch_xor(led, Ordering::Relaxed);
+    LEDS_DIRTY.store(true, Ordering::Release);
+}
+
+/// Sends lock state changed since the last call to the LEDs. Call from
+/// the kernel loop, never from interrupt context.
+pub fn poll() {
+    if !LEDS_DIRTY.swap(false, Ordering::Acquire) || !is_present() {
+        return;
+    }
+    let result = interrupts::without_interrupts(|| set_leds(locks()));
+    if let Err(e) = result {
+        if crate::drivers::report_error("ps2_keyboard", &e) {
+            let _ = interrupts::without_interrupts(|| set_leds(locks()));
+        }
+    }
+}
+
+/// IRQ1 body: reads and processes one byte if the controller has one.
+pub fn handle_irq() {
+    let Some(byte) = i8042::try_read_data() else {
+        // Byte already taken by a command poll
+        return;
+    };
+    if matches!(byte, RESP_ACK | RESP_RESEND) || !is_present() {
+        return;
+    }
+    process_scancode(byte);
+}
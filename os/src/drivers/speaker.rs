@@ -0,0 +1,67 @@
+//! PC speaker driven by PIT channel 2.
+//!
+//! Channel 2 generates a square wave whose output is gated to the speaker
+//! through port 0x61 (bit 0: timer 2 gate, bit 1: speaker data enable).
+//!
+//! Timing does not depend on interrupts (`pit::delay_ms` polls the DRAM
+//! refresh bit), so `beep` also works from the panic handler with
+//! interrupts disabled.
+
+use crate::arch::x86::{pit::{self, PIT_BASE_HZ}, port::PortRange};
+use crate::kernel::resource;
+
+// Port offsets: channel 2 data and the PIT mode/command register
+const CH2_DATA: u16 = 0;
+const CMD: u16 = 1;
+
+// Claimed in `init`; 0x61 is shared with the PIT delay loop
+static PORTS: PortRange = unsafe { PortRange::new(0x42, 2) };
+static SPEAKER_CTRL: PortRange = unsafe { PortRange::new(0x61, 1) };
+
+/// Command: channel 2, lo/hi bytes, mode 3 (square wave), binary
+const CMD_CH2_SQUARE: u8 = 0xB6;
+
+const GATE_TIMER2: u8 = 0x01;
+const SPEAKER_ENABLE: u8 = 0x02;
+
+/// Audible range accepted by `tone_on`
+const MIN_HZ: u32 = 20;
+const MAX_HZ: u32 = 20_000;
+
+/// Starts a continuous tone at `freq_hz` (clamped to 20 Hz..20 kHz).
+pub fn tone_on(freq_hz: u32) {
+    let freq = freq_hz.clamp(MIN_HZ, MAX_HZ);
+    let divisor = PIT_BASE_HZ / freq;
+
+    PORTS.write8(CMD, CMD_CH2_SQUARE);
+    PORTS.write8(CH2_DATA, (divisor & 0xFF) as u8);
+    PORTS.write8(CH2_DATA, (divisor >> 8) as u8);
+
+    let ctrl = SPEAKER_CTRL.read8(0);
+    SPEAKER_CTRL.write8(0, ctrl | GATE_TIMER2 | SPEAKER_ENABLE);
+}
+
+/// Silences the speaker.
+pub fn tone_off() {
+    let ctrl = SPEAKER_CTRL.read8(0);
+    SPEAKER_CTRL.write8(0, ctrl & !(GATE_TIMER2 | SPEAKER_ENABLE));
+}
+
+/// Plays `freq_hz` for `ms` milliseconds. Blocks the caller.
+pub fn beep(freq_hz: u32, ms: u32) {
+    tone_on(freq_hz);
+    pit::delay_ms(ms);
+    tone_off();
+}
+
+/// Silences a speaker left on by firmware.
+pub fn init() {
+    if let Err(e) = PORTS
+        .claim("pc speaker", 0)
+        .and_then(|_| SPEAKER_CTRL.claim("system control B", resource::SHARED))
+    {
+        resource::log_conflict("speaker", e);
+        return;
+    }
+    tone_off();
+}
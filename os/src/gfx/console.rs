@@ -0,0 +1,33 @@
+//! Minimal text console on the framebuffer
+//!
+//! Appends text line by line with the 8x8 font. At the bottom of the
+//! screen it wraps to the top and clears each line before reusing it;
+//! there is no scrolling (that would need a full-screen copy per line).
+//! Characters past the right edge are dropped.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use super::{font, Color};
+
+/// Text row the next line is written to
+static ROW: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes `text`; every `\n` ends a line. No-op without a framebuffer.
+pub fn write(text: &str, fg: Color) {
+    super::with(|surface| {
+        let info = surface.info();
+        let rows = info.height / font::HEIGHT;
+        let cols = info.width / font::WIDTH;
+        if rows == 0 {
+            return;
+        }
+        for line in text.split_terminator('\n') {
+            let row = ROW.fetch_add(1, Ordering::Relaxed) % rows;
+            let y = row * font::HEIGHT;
+            surface.fill_rect(0, y, info.width, font::HEIGHT, Color::BLACK);
+            for (col, c) in line.chars().take(cols).enumerate() {
+                surface.draw_char(col * font::WIDTH, y, c, fg, Color::BLACK);
+            }
+        }
+    });
+    super::present();
+}
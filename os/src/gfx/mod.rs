@@ -0,0 +1,281 @@
+//! Framebuffer graphics primitives
+//!
+//! Draws into the linear framebuffer negotiated by the bootloader (UEFI GOP
+//! or VBE). BIOS boots without a framebuffer simply leave this module
+//! uninitialized and every drawing call becomes a no-op.
+//!
+//! # Double buffering
+//! Drawing always targets the back buffer when one is attached with
+//! `attach_back_buffer()`, and `present()` copies it to the screen. Without
+//! one, drawing goes straight to the framebuffer. The kernel has no heap
+//! yet, so the back buffer must be provided by the caller.
+//!
+//! # Pixel formats
+//! RGB and BGR with 3 or 4 bytes per pixel, and 8-bit grayscale. Other
+//! formats are rejected at `init()`.
+
+pub mod console;
+pub mod font;
+
+use bootloader_api::info::{FrameBufferInfo, PixelFormat};
+use x86_64::instructions::interrupts;
+
+/// 24-bit color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Self = Self::rgb(0, 0, 0);
+    pub const WHITE: Self = Self::rgb(0xFF, 0xFF, 0xFF);
+    pub const RED: Self = Self::rgb(0xFF, 0, 0);
+    pub const GREEN: Self = Self::rgb(0, 0xFF, 0);
+    pub const BLUE: Self = Self::rgb(0, 0, 0xFF);
+    pub const GRAY: Self = Self::rgb(0xAA, 0xAA, 0xAA);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Builds a color from 0x00RRGGBB.
+    pub const fn from_u32(rgb: u32) -> Self {
+        Self::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+}
+
+/// Errors from framebuffer setup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GfxError {
+    /// Pixel format or depth not supported by the drawing code
+    UnsupportedFormat,
+    /// Buffer is smaller than `stride * height * bytes_per_pixel`
+    BufferTooSmall,
+    /// `init()` has not been called (no framebuffer)
+    NotInitialized,
+}
+
+/// Pixel buffer with a known layout (screen or off-screen).
+pub struct Surface {
+    base: *mut u8,
+    len: usize,
+    info: FrameBufferInfo,
+}
+
+impl Surface {
+    /// Wraps a raw pixel buffer.
+    ///
+    /// # Safety
+    /// `base..base + len` must be valid, writable and not accessed through
+    /// any other path while the surface exists.
+    pub unsafe fn new(base: *mut u8, len: usize, info: FrameBufferInfo) -> Result<Self, GfxError> {
+        match (info.pixel_format, info.bytes_per_pixel) {
+            (PixelFormat::Rgb | PixelFormat::Bgr, 3 | 4) | (PixelFormat::U8, 1) => {}
+            _ => return Err(GfxError::UnsupportedFormat),
+        }
+        if len < info.stride * info.height * info.bytes_per_pixel {
+            return Err(GfxError::BufferTooSmall);
+        }
+        Ok(Self { base, len, info })
+    }
+
+    /// Returns the layout of this surface.
+    pub fn info(&self) -> FrameBufferInfo {
+        self.info
+    }
+
+    #[inline]
+    fn bytes(&mut self) -> &mut [u8] {
+        // SAFETY: guaranteed by `Surface::new`
+        unsafe { core::slice::from_raw_parts_mut(self.base, self.len) }
+    }
+
+    #[inline]
+    fn encode(&self, color: Color) -> [u8; 4] {
+        match self.info.pixel_format {
+            PixelFormat::Bgr => [color.b, color.g, color.r, 0],
+            PixelFormat::U8 => {
+                let y = (color.r as u16 * 77 + color.g as u16 * 150 + color.b as u16 * 29) >> 8;
+                [y as u8, 0, 0, 0]
+            }
+            _ => [color.r, color.g, color.b, 0],
+        }
+    }
+
+    /// Sets one pixel. Out-of-bounds coordinates are ignored.
+    pub fn put_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x >= self.info.width || y >= self.info.height {
+            return;
+        }
+        let bpp = self.info.bytes_per_pixel;
+        let offset = (y * self.info.stride + x) * bpp;
+        let px = self.encode(color);
+        self.bytes()[offset..offset + bpp].copy_from_slice(&px[..bpp]);
+    }
+
+    /// Fills a rectangle, clipped to the surface.
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: Color) {
+        let x_end = x.saturating_add(w).min(self.info.width);
+        let y_end = y.saturating_add(h).min(self.info.height);
+        if x >= x_end || y >= y_end {
+            return;
+        }
+
+        let bpp = self.info.bytes_per_pixel;
+        let stride = self.info.stride;
+        let px = self.encode(color);
+        let bytes = self.bytes();
+        for row in y..y_end {
+            let start = (row * stride + x) * bpp;
+            let end = (row * stride + x_end) * bpp;
+            for dst in bytes[start..end].chunks_exact_mut(bpp) {
+                dst.copy_from_slice(&px[..bpp]);
+            }
+        }
+    }
+
+    /// Clears the whole surface.
+    pub fn clear(&mut self, color: Color) {
+        self.fill_rect(0, 0, self.info.width, self.info.height, color);
+    }
+
+    /// Copies a `w` x `h` image of 0x00RRGGBB pixels to (x, y), clipped.
+    pub fn blit(&mut self, x: usize, y: usize, w: usize, h: usize, pixels: &[u32]) {
+        for row in 0..h.min(pixels.len() / w.max(1)) {
+            for col in 0..w {
+                self.put_pixel(x + col, y + row, Color::from_u32(pixels[row * w + col]));
+            }
+        }
+    }
+
+    /// Copies the contents of `src` (same layout) into this surface.
+    pub fn copy_from(&mut self, src: &mut Surface) {
+        let len = self.len.min(src.len);
+        self.bytes()[..len].copy_from_slice(&src.bytes()[..len]);
+    }
+
+    /// Draws one 8x8 glyph with a solid background.
+    pub fn draw_char(&mut self, x: usize, y: usize, c: char, fg: Color, bg: Color) {
+        let glyph = font::glyph(c);
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..font::WIDTH {
+                let color = if bits & (1 << col) != 0 { fg } else { bg };
+                self.put_pixel(x + col, y + row, color);
+            }
+        }
+    }
+
+    /// Draws a string starting at (x, y). `\n` starts a new line at `x`.
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, fg: Color, bg: Color) {
+        let (mut cx, mut cy) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                cx = x;
+                cy += font::HEIGHT;
+                continue;
+            }
+            self.draw_char(cx, cy, c, fg, bg);
+            cx += font::WIDTH;
+        }
+    }
+}
+
+/// Screen and optional back buffer
+struct Gfx {
+    front: Surface,
+    back: Option<Surface>,
+}
+
+impl Gfx {
+    fn target(&mut self) -> &mut Surface {
+        self.back.as_mut().unwrap_or(&mut self.front)
+    }
+}
+
+static mut GFX: Option<Gfx> = None;
+
+/// Publishes the bootloader framebuffer to the graphics layer.
+///
+/// # Safety
+/// `buffer` must be the framebuffer mapping described by `info`, and must
+/// not be written through any other path afterwards.
+pub unsafe fn init(buffer: &'static mut [u8], info: FrameBufferInfo) -> Result<(), GfxError> {
+    let front = Surface::new(buffer.as_mut_ptr(), buffer.len(), info)?;
+    interrupts::without_interrupts(|| {
+        *(&raw mut GFX) = Some(Gfx { front, back: None });
+    });
+    Ok(())
+}
+
+/// Attaches an off-screen buffer; drawing goes there until `present()`.
+///
+/// The buffer must be at least as large as the screen buffer and outlive
+/// the graphics layer.
+pub fn attach_back_buffer(buffer: &'static mut [u8]) -> Result<(), GfxError> {
+    interrupts::without_interrupts(|| unsafe {
+        let gfx = (*(&raw mut GFX)).as_mut().ok_or(GfxError::NotInitialized)?;
+        let info = gfx.front.info();
+        gfx.back = Some(Surface::new(buffer.as_mut_ptr(), buffer.len(), info)?);
+        Ok(())
+    })
+}
+
+/// Runs `f` on the current drawing target. Returns `None` if there is no
+/// framebuffer.
+pub fn with<R>(f: impl FnOnce(&mut Surface) -> R) -> Option<R> {
+    interrupts::without_interrupts(|| unsafe {
+        (*(&raw mut GFX)).as_mut().map(|gfx| f(gfx.target()))
+    })
+}
+
+/// Copies the back buffer to the screen (no-op without one).
+pub fn present() {
+    interrupts::without_interrupts(|| unsafe {
+        if let Some(Gfx { front, back: Some(back) }) = (*(&raw mut GFX)).as_mut() {
+            front.copy_from(back);
+        }
+    });
+}
+
+/// Returns the size of the screen buffer mapping in bytes.
+pub fn mapped_len() -> Option<usize> {
+    interrupts::without_interrupts(|| unsafe { (*(&raw const GFX)).as_ref().map(|g| g.front.len) })
+}
+
+/// Re-publishes the screen after a mode change.
+///
+/// The framebuffer keeps its base address and mapping; only the layout
+/// changes. A back buffer too small for the new layout is detached.
+pub fn set_mode_info(info: FrameBufferInfo) -> Result<(), GfxError> {
+    interrupts::without_interrupts(|| unsafe {
+        let gfx = (*(&raw mut GFX)).as_mut().ok_or(GfxError::NotInitialized)?;
+        gfx.front = Surface::new(gfx.front.base, gfx.front.len, info)?;
+        gfx.back = gfx
+            .back
+            .take()
+            .and_then(|back| Surface::new(back.base, back.len, info).ok());
+        Ok(())
+    })
+}
+
+/// Returns the screen layout, if a framebuffer is available.
+pub fn info() -> Option<FrameBufferInfo> {
+    interrupts::without_interrupts(|| unsafe { (*(&raw const GFX)).as_ref().map(|g| g.front.info()) })
+}
+
+/// Fills a rectangle on the current drawing target.
+pub fn fill_rect(x: usize, y: usize, w: usize, h: usize, color: Color) {
+    with(|s| s.fill_rect(x, y, w, h, color));
+}
+
+/// Copies 0x00RRGGBB pixels to the current drawing target.
+pub fn blit(x: usize, y: usize, w: usize, h: usize, pixels: &[u32]) {
+    with(|s| s.blit(x, y, w, h, pixels));
+}
+
+/// Draws text on the current drawing target.
+pub fn draw_text(x: usize, y: usize, text: &str, fg: Color, bg: Color) {
+    with(|s| s.draw_text(x, y, text, fg, bg));
+}
@@ -0,0 +1,72 @@
+//! Kernel command line
+//!
+//! Read once at boot from fw_cfg `opt/os/cmdline`
+//! (`OS_CMDLINE="pic=off" ./run-qemu.sh`). Words are separated by spaces
+//! and are either `key=value` or a bare `flag`. Missing fw_cfg or file
+//! means an empty command line.
+
+use crate::drivers::fw_cfg;
+use crate::serial;
+
+/// Longer command lines are truncated
+const MAX_LEN: usize = 256;
+
+static mut CMDLINE: [u8; MAX_LEN] = [0; MAX_LEN];
+static mut CMDLINE_LEN: usize = 0;
+
+/// Loads the command line from fw_cfg. Call once, after `fw_cfg::init`.
+pub fn init() {
+    if !fw_cfg::is_present() {
+        return;
+    }
+    unsafe {
+        let cmdline = &raw mut CMDLINE;
+        let buf = &mut *cmdline;
+        if let Some(len) = fw_cfg::read_file_by_name(fw_cfg::CMDLINE_FILE, buf) {
+            CMDLINE_LEN = len.min(MAX_LEN);
+        }
+    }
+    serial::write_fmt(format_args!("cmdline: \"{}\"\n", as_str()));
+}
+
+/// Returns the whole command line.
+pub fn as_str() -> &'static str {
+    unsafe {
+        let cmdline = &raw const CMDLINE;
+        let buf = &*cmdline;
+        let bytes = &buf[..CMDLINE_LEN];
+        core::str::from_utf8(bytes).unwrap_or("").trim_end_matches(['\0', '\n'])
+    }
+}
+
+/// Returns the value of `key=value`, if present (last occurrence wins).
+pub fn get(key: &str) -> Option<&'static str> {
+    find_value(as_str(), key)
+}
+
+/// Returns true if the bare word `flag` is present.
+pub fn has_flag(flag: &str) -> bool {
+    as_str().split_ascii_whitespace().any(|word| word == flag)
+}
+
+fn find_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.split_ascii_whitespace()
+        .rev()
+        .filter_map(|word| word.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_value() {
+        assert_eq!(find_value("pic=off quiet", "pic"), Some("off"));
+        assert_eq!(find_value("pic=on pic=off", "pic"), Some("off"));
+        assert_eq!(find_value("quiet", "quiet"), None);
+        assert_eq!(find_value("epic=off", "pic"), None);
+        assert_eq!(find_value("", "pic"), None);
+    }
+}
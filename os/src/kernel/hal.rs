@@ -0,0 +1,106 @@
+//! Hardware abstraction consumed by kernel logic
+//!
+//! Tick accounting and log formatting talk to the timer, the interrupt
+//! controller and the UART through these traits instead of calling the
+//! drivers directly. The x86 implementation is `arch::x86::hal::X86`;
+//! `mock` provides recording implementations for host-side unit tests, so
+//! that logic can be checked without booting QEMU.
+
+/// Periodic tick source
+pub trait Timer {
+    /// Tick frequency in Hz
+    fn tick_hz(&self) -> u32;
+    /// Busy-waits about `ms` milliseconds without relying on interrupts.
+    fn delay_ms(&self, ms: u32);
+}
+
+/// Interrupt controller (8259 pair or LAPIC)
+pub trait InterruptController {
+    fn unmask(&self, irq: u8);
+    fn end_of_interrupt(&self, irq: u8);
+}
+
+/// Byte-oriented debug output
+pub trait Uart {
+    fn write_byte(&self, byte: u8);
+
+    fn write_str(&self, s: &str) {
+        for b in s.bytes() {
+            self.write_byte(b);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use core::cell::{Cell, RefCell};
+
+    /// Timer that advances a virtual clock instead of waiting
+    pub struct MockTimer {
+        pub hz: u32,
+        pub elapsed_ms: Cell<u64>,
+    }
+
+    impl MockTimer {
+        pub fn new(hz: u32) -> Self {
+            Self { hz, elapsed_ms: Cell::new(0) }
+        }
+    }
+
+    impl Timer for MockTimer {
+        fn tick_hz(&self) -> u32 {
+            self.hz
+        }
+        fn delay_ms(&self, ms: u32) {
+            self.elapsed_ms.set(self.elapsed_ms.get() + ms as u64);
+        }
+    }
+
+    /// Records unmasked lines and EOIs
+    #[derive(Default)]
+    pub struct MockIrq {
+        pub unmasked: Cell<u16>,
+        pub eoi_count: Cell<u32>,
+        pub last_eoi: Cell<Option<u8>>,
+    }
+
+    impl InterruptController for MockIrq {
+        fn unmask(&self, irq: u8) {
+            self.unmasked.set(self.unmasked.get() | 1 << irq);
+        }
+        fn end_of_interrupt(&self, irq: u8) {
+            self.eoi_count.set(self.eoi_count.get() + 1);
+            self.last_eoi.set(Some(irq));
+        }
+    }
+
+    /// Collects the first `CAPACITY` bytes written
+    pub struct MockUart {
+        buf: RefCell<[u8; MockUart::CAPACITY]>,
+        len: Cell<usize>,
+    }
+
+    impl MockUart {
+        pub const CAPACITY: usize = 256;
+
+        pub fn new() -> Self {
+            Self { buf: RefCell::new([0; Self::CAPACITY]), len: Cell::new(0) }
+        }
+
+        /// Everything written so far.
+        pub fn output(&self) -> ([u8; Self::CAPACITY], usize) {
+            (*self.buf.borrow(), self.len.get())
+        }
+    }
+
+    impl Uart for MockUart {
+        fn write_byte(&self, byte: u8) {
+            let len = self.len.get();
+            if len < Self::CAPACITY {
+                self.buf.borrow_mut()[len] = byte;
+                self.len.set(len + 1);
+            }
+        }
+    }
+}
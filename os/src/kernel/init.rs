@@ -13,10 +13,10 @@ pub struct KernelState {
 }
 
 pub fn early_init(
-    boot_info: &'static BootInfo,
+    boot_info: &'static mut BootInfo,
 ) -> Result<KernelState, KernelInitError> {
-    serial::init();
     serial::write_str("Kernel is running\n");
+    crate::kernel::manifest::log();
 
     if crate::long_mode::is_long_mode() {
         serial::write_str("64-bit long mode\n");
@@ -34,6 +34,13 @@ pub fn early_init(
         }
     }
 
+    // Framebuffer memory goes to the graphics layer; boot_info keeps only
+    // the description from here on.
+    init_framebuffer(boot_info);
+    let boot_info: &'static BootInfo = boot_info;
+
+    crate::arch::x86::gdt::stack::track_boot_stack(boot_info.kernel_stack_bottom, boot_info.kernel_stack_len);
+
     // GDT / IDT initialization
     crate::arch::x86::gdt::init();
     serial::write_str("GDT loaded\n");
@@ -43,16 +50,41 @@ pub fn early_init(
     let paging = unsafe { crate::paging::init(boot_info) }
     .map_err(|_| KernelInitError::PagingInitFailed)?;
     serial::write_str("paging: init OK (bootloader tables)\n");
+    crate::kernel::resource::register_memory_map(&boot_info.memory_regions);
 
     // IDT initialization
     crate::arch::x86::idt::init();
     serial::write_str("IDT loaded\n");
 
+    // CET needs the #CP handler in place
+    crate::arch::x86::cet::init();
+
+    // Host-provided configuration (QEMU only); the command line picks the
+    // interrupt controller below
+    crate::drivers::fw_cfg::init();
+    crate::kernel::cmdline::init();
+    crate::kernel::log::init();
+    crate::arch::x86::spec_ctrl::init();
+
     // PIC / PIT initialization
     crate::arch::x86::pic::init();
     crate::arch::x86::pit::init();
+    crate::kernel::irqsoff::init();
+    if crate::kernel::cmdline::get("pic") == Some("off") {
+        init_apic_mode(boot_info);
+    }
     interrupts::enable();
-    serial::write_str("PIC / PIT initialized; PIT 100 Hz; timer enabled\n");
+    if crate::arch::x86::pic::is_disabled() {
+        serial::write_str("PIC masked; LAPIC timer 100 Hz; timer enabled\n");
+    } else {
+        serial::write_str("PIC / PIT initialized; PIT 100 Hz; timer enabled\n");
+    }
+
+    // Platform drivers (blacklist= / probe_order= apply)
+    crate::drivers::init_all();
+    crate::kernel::resource::log_tree();
+    // Every sink that will come up is up by now
+    crate::kernel::log::early::close();
 
     Ok(KernelState {
         paging,
@@ -60,8 +92,78 @@ pub fn early_init(
     })
 }
 
+/// Switches to APIC-native interrupts (`pic=off`): LAPIC timer for the
+/// tick, both PICs masked. Falls back to the PIC if the LAPIC is unusable.
+fn init_apic_mode(boot_info: &BootInfo) {
+    use crate::arch::x86::{lapic, pic, pit};
+
+    let bootloader_api::info::Optional::Some(offset) = boot_info.physical_memory_offset else {
+        serial::write_str("lapic: no physical memory window, keeping PIC\n");
+        return;
+    };
+
+    match unsafe { lapic::init(x86_64::VirtAddr::new(offset)) } {
+        Ok(()) => {
+            pic::disable();
+            let per_tick = lapic::start_timer(pit::TICK_HZ);
+            serial::write_fmt(format_args!(
+                "lapic: id {} version {:#x}, timer {} counts/tick\n",
+                lapic::id(),
+                lapic::version(),
+                per_tick
+            ));
+        }
+        Err(e) => serial::write_fmt(format_args!("lapic: {:?}, keeping PIC\n", e)),
+    }
+}
+
+fn init_framebuffer(boot_info: &mut BootInfo) {
+    let bootloader_api::info::Optional::Some(fb) = &mut boot_info.framebuffer else {
+        return;
+    };
+    let info = fb.info();
+    let buffer = fb.buffer_mut();
+    // SAFETY: the framebuffer mapping lives for the whole kernel lifetime and
+    // nothing else writes to it once it is handed to gfx.
+    let buffer = unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr(), buffer.len()) };
+
+    match unsafe { crate::gfx::init(buffer, info) } {
+        Ok(()) => {
+            crate::gfx::with(|s| {
+                s.clear(crate::gfx::Color::BLACK);
+                s.draw_text(8, 8, "Kernel is running", crate::gfx::Color::WHITE, crate::gfx::Color::BLACK);
+            });
+            crate::kernel::log::mark_ready("fb");
+            serial::write_fmt(format_args!(
+                "gfx: framebuffer {}x{} {:?} {} bpp\n",
+                info.width, info.height, info.pixel_format, info.bytes_per_pixel * 8
+            ));
+        }
+        Err(e) => serial::write_fmt(format_args!("gfx: framebuffer unusable: {:?}\n", e)),
+    }
+}
+
+/// Ticks between stack high-water checks (1 s)
+const STACK_CHECK_TICKS: u64 = crate::arch::x86::pit::TICK_HZ as u64;
+
 pub fn kernel_loop(_state: KernelState) -> ! {
+    use crate::arch::x86::{gdt::stack, idt::storage::TICK_COUNT};
+    use core::sync::atomic::Ordering;
+
+    serial::write_str("Stack high-water marks after init:\n");
+    stack::log_usage();
+    crate::kernel::irqsoff::log_report();
+
+    let mut last_check = 0;
     loop {
         x86_64::instructions::hlt();
+
+        crate::drivers::ps2_keyboard::poll();
+
+        let ticks = TICK_COUNT.load(Ordering::Relaxed);
+        if ticks.wrapping_sub(last_check) >= STACK_CHECK_TICKS {
+            last_check = ticks;
+            stack::check_all();
+        }
     }
 }
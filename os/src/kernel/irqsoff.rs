@@ -0,0 +1,160 @@
+//! IRQ-off / preempt-off region tracker
+//!
+//! With `trace_irqsoff` on the command line, every outermost region with
+//! interrupts disabled by an `*_irqsave` lock, and every outermost
+//! `preempt_disable` .. `preempt_enable` region, is timed with the TSC.
+//! The `TOP_N` longest of each kind are kept together with the call site
+//! that opened them (`#[track_caller]` through the lock and preempt APIs).
+//!
+//! Regions opened by `interrupts::without_interrupts` or by the CPU on
+//! interrupt entry are not seen. Off by default: it adds two `rdtsc` to
+//! every lock round trip.
+
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use x86_64::instructions::interrupts;
+use crate::kernel::log::log_info;
+
+/// Longest regions kept per kind
+pub const TOP_N: usize = 4;
+
+/// Calibration window for the TSC rate
+const CALIBRATION_MS: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    IrqsOff,
+    PreemptOff,
+}
+
+/// One measured region
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    pub cycles: u64,
+    /// Where the region was opened
+    pub location: &'static Location<'static>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TSC_PER_US: AtomicU64 = AtomicU64::new(1);
+
+/// Longest regions, longest first; only touched with interrupts disabled
+static mut WORST_IRQS_OFF: [Option<Region>; TOP_N] = [None; TOP_N];
+static mut WORST_PREEMPT_OFF: [Option<Region>; TOP_N] = [None; TOP_N];
+
+/// Start of the open preempt-off region (one CPU, one region)
+static mut PREEMPT_OFF_START: Option<(u64, &'static Location<'static>)> = None;
+
+#[inline(always)]
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// TSC timestamp if tracing is on.
+#[inline(always)]
+pub fn start() -> Option<u64> {
+    ENABLED.load(Ordering::Relaxed).then(rdtsc)
+}
+
+/// Closes a region opened at `start` by `location`.
+pub fn record(kind: RegionKind, start: u64, location: &'static Location<'static>) {
+    let region = Region { cycles: rdtsc().wrapping_sub(start), location };
+    interrupts::without_interrupts(|| unsafe {
+        let table = match kind {
+            RegionKind::IrqsOff => &raw mut WORST_IRQS_OFF,
+            RegionKind::PreemptOff => &raw mut WORST_PREEMPT_OFF,
+        };
+        insert(&mut *table, region);
+    });
+}
+
+/// Called by `preempt_disable` when the count leaves zero.
+pub fn preempt_off_begin(location: &'static Location<'static>) {
+    if let Some(tsc) = start() {
+        interrupts::without_interrupts(|| unsafe {
+            let open = &raw mut PREEMPT_OFF_START;
+            *open = Some((tsc, location));
+        });
+    }
+}
+
+/// Called by `preempt_enable` when the count returns to zero.
+pub fn preempt_off_end() {
+    let open = interrupts::without_interrupts(|| unsafe {
+        let open = &raw mut PREEMPT_OFF_START;
+        (*open).take()
+    });
+    if let Some((tsc, location)) = open {
+        record(RegionKind::PreemptOff, tsc, location);
+    }
+}
+
+/// Keeps `table` sorted longest first, dropping the shortest on overflow.
+fn insert(table: &mut [Option<Region>; TOP_N], region: Region) {
+    let Some(pos) = table
+        .iter()
+        .position(|slot| slot.is_none_or(|r| region.cycles > r.cycles))
+    else {
+        return;
+    };
+    table[pos..].rotate_right(1);
+    table[pos] = Some(region);
+}
+
+/// Calibrates the TSC and enables tracing if `trace_irqsoff` is given.
+///
+/// Needs the PIT (for `delay_ms`) and the command line.
+pub fn init() {
+    if !crate::kernel::cmdline::has_flag("trace_irqsoff") {
+        return;
+    }
+    let t0 = rdtsc();
+    crate::arch::x86::pit::delay_ms(CALIBRATION_MS);
+    let per_us = (rdtsc() - t0) / (CALIBRATION_MS as u64 * 1000);
+    TSC_PER_US.store(per_us.max(1), Ordering::Relaxed);
+    ENABLED.store(true, Ordering::Relaxed);
+    log_info!("irqsoff: tracing on, {} TSC cycles/us", per_us);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Logs the longest regions of both kinds.
+pub fn log_report() {
+    if !is_enabled() {
+        return;
+    }
+    let per_us = TSC_PER_US.load(Ordering::Relaxed);
+    let (irqs, preempt) = interrupts::without_interrupts(|| unsafe {
+        let (irqs, preempt) = (&raw const WORST_IRQS_OFF, &raw const WORST_PREEMPT_OFF);
+        (*irqs, *preempt)
+    });
+    for (title, table) in [("irqs off", irqs), ("preempt off", preempt)] {
+        for region in table.iter().flatten() {
+            log_info!(
+                "irqsoff: {} {} us at {}:{}",
+                title,
+                region.cycles / per_us,
+                region.location.file(),
+                region.location.line()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_keeps_longest() {
+        let location = Location::caller();
+        let mut table = [None; TOP_N];
+        for cycles in [5, 1, 9, 3, 7, 2] {
+            insert(&mut table, Region { cycles, location });
+        }
+        let kept: [u64; TOP_N] = core::array::from_fn(|i| table[i].unwrap().cycles);
+        assert_eq!(kept, [9, 7, 5, 3]);
+    }
+}
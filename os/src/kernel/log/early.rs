@@ -0,0 +1,107 @@
+//! Early-boot log buffer
+//!
+//! Sinks start out not ready (the serial port before `serial::init`, the
+//! framebuffer before `gfx::init`). Until `close` is called at the end of
+//! early init, every record is also kept here, and `super::mark_ready`
+//! replays the buffer into a sink as it comes up, so nothing logged before
+//! it was available is lost. A sink that never comes up gets nothing.
+//!
+//! Records are stored as `[level][len lo][len hi][text]`. When the buffer
+//! is full, further records are counted and reported after the replay.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::sync::RwLock;
+use super::Level;
+
+pub const EARLY_SIZE: usize = 8 * 1024;
+
+const HEADER: usize = 3;
+
+struct EarlyBuf {
+    buf: [u8; EARLY_SIZE],
+    len: usize,
+}
+
+impl EarlyBuf {
+    const fn new() -> Self {
+        Self { buf: [0; EARLY_SIZE], len: 0 }
+    }
+
+    /// Appends one record; false if it does not fit.
+    fn push(&mut self, level: Level, text: &str) -> bool {
+        let end = self.len + HEADER + text.len();
+        if end > EARLY_SIZE {
+            return false;
+        }
+        let len = text.len() as u16;
+        self.buf[self.len] = level as u8;
+        self.buf[self.len + 1..self.len + HEADER].copy_from_slice(&len.to_le_bytes());
+        self.buf[self.len + HEADER..end].copy_from_slice(text.as_bytes());
+        self.len = end;
+        true
+    }
+
+    /// Calls `f` for each stored record in order.
+    fn for_each(&self, mut f: impl FnMut(Level, &str)) {
+        let mut pos = 0;
+        while pos < self.len {
+            let level = LEVELS[self.buf[pos] as usize - 1];
+            let len = u16::from_le_bytes([self.buf[pos + 1], self.buf[pos + 2]]) as usize;
+            let text = &self.buf[pos + HEADER..pos + HEADER + len];
+            // Stored from a &str
+            f(level, unsafe { core::str::from_utf8_unchecked(text) });
+            pos += HEADER + len;
+        }
+    }
+}
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+static EARLY: RwLock<EarlyBuf> = RwLock::new(EarlyBuf::new());
+static OPEN: AtomicBool = AtomicBool::new(true);
+static LOST: AtomicU32 = AtomicU32::new(0);
+
+/// True until `close`.
+pub fn is_open() -> bool {
+    OPEN.load(Ordering::Relaxed)
+}
+
+/// Keeps a formatted record for later replay.
+pub fn record(level: Level, text: &str) {
+    if is_open() && !EARLY.write_irqsave().push(level, text) {
+        LOST.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Replays every stored record through `f`; returns how many were lost.
+pub fn replay(f: impl FnMut(Level, &str)) -> u32 {
+    EARLY.read_irqsave().for_each(f);
+    LOST.load(Ordering::Relaxed)
+}
+
+/// Stops recording. Sinks that become ready later start empty.
+pub fn close() {
+    OPEN.store(false, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_and_replay() {
+        let mut early = EarlyBuf::new();
+        assert!(early.push(Level::Warn, "a\n"));
+        assert!(early.push(Level::Debug, "bc\n"));
+        let mut seen = 0;
+        early.for_each(|level, text| {
+            match seen {
+                0 => assert_eq!((level, text), (Level::Warn, "a\n")),
+                _ => assert_eq!((level, text), (Level::Debug, "bc\n")),
+            }
+            seen += 1;
+        });
+        assert_eq!(seen, 2);
+        assert!(!early.push(Level::Info, core::str::from_utf8(&[b'x'; EARLY_SIZE]).unwrap()));
+    }
+}
@@ -0,0 +1,285 @@
+//! Kernel logging facade
+//!
+//! `log_error!` .. `log_trace!` format a record once and hand it to every
+//! sink whose minimum level admits it:
+//! - `serial`: COM1
+//! - `fb`: text console on the framebuffer (`gfx::console`)
+//! - `ring`: in-memory buffer kept for later inspection (`ring`)
+//!
+//! There is no network stack, so there is no netconsole sink.
+//!
+//! # Configuration
+//! - `loglevel=<level>` sets every sink's minimum level
+//! - `loglevel.<sink>=<level>` overrides it for one sink
+//! - `lograte.<sink>=<n>` allows at most `n` records per second on a sink
+//!   (0 = unlimited); dropped records are counted and reported once the
+//!   next second starts
+//!
+//! Levels are `error`, `warn`, `info`, `debug`, `trace`. Records longer
+//! than `MAX_RECORD` bytes are truncated.
+//!
+//! Records logged before a sink is ready (`mark_ready`) are kept in the
+//! `early` buffer and replayed into it once it is.
+//!
+//! Paths that can fire in a loop (exceptions, stray IRQs) use
+//! `log_ratelimited!` instead (see `ratelimit`).
+
+pub mod early;
+pub mod ratelimit;
+pub mod ring;
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use crate::arch::x86::{idt::storage::TICK_COUNT, pit::TICK_HZ};
+use crate::kernel::cmdline;
+
+/// Longest formatted record, including the level tag
+pub const MAX_RECORD: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+            .into_iter()
+            .find(|l| l.name() == s)
+    }
+}
+
+/// One output of the facade
+pub struct Sink {
+    pub name: &'static str,
+    write: fn(Level, &str),
+    /// Minimum level as `Level as u8` (records above it are dropped)
+    max_level: AtomicU8,
+    /// Records per second, 0 = unlimited
+    rate: AtomicU32,
+    window_start: AtomicU64,
+    window_count: AtomicU32,
+    suppressed: AtomicU32,
+    /// Hardware behind the sink is initialized
+    ready: AtomicBool,
+}
+
+impl Sink {
+    const fn new(name: &'static str, write: fn(Level, &str), level: Level, rate: u32, ready: bool) -> Self {
+        Self {
+            name,
+            write,
+            ready: AtomicBool::new(ready),
+            max_level: AtomicU8::new(level as u8),
+            rate: AtomicU32::new(rate),
+            window_start: AtomicU64::new(0),
+            window_count: AtomicU32::new(0),
+            suppressed: AtomicU32::new(0),
+        }
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        level as u8 <= self.max_level.load(Ordering::Relaxed)
+    }
+
+    /// Charges one record to the current one-second window.
+    ///
+    /// Returns false if the sink is over its rate; reports what was
+    /// suppressed in the previous window when a new one starts.
+    fn admit(&self) -> bool {
+        let rate = self.rate.load(Ordering::Relaxed);
+        if rate == 0 {
+            return true;
+        }
+        let now = TICK_COUNT.load(Ordering::Relaxed);
+        let start = self.window_start.load(Ordering::Relaxed);
+        if now.wrapping_sub(start) >= TICK_HZ as u64
+            && self
+                .window_start
+                .compare_exchange(start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.window_count.store(0, Ordering::Relaxed);
+            let dropped = self.suppressed.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                let mut line = LineBuf::new();
+                let _ = writeln!(line, "[warn] log: {} records suppressed", dropped);
+                (self.write)(Level::Warn, line.as_str());
+            }
+        }
+        if self.window_count.fetch_add(1, Ordering::Relaxed) < rate {
+            true
+        } else {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+pub static SINKS: [Sink; 3] = [
+    Sink::new("serial", write_serial, Level::Info, 0, false),
+    Sink::new("fb", write_fb, Level::Warn, 20, false),
+    Sink::new("ring", ring::write, Level::Debug, 0, true),
+];
+
+fn write_serial(_level: Level, text: &str) {
+    crate::serial::write_str(text);
+}
+
+fn write_fb(level: Level, text: &str) {
+    use crate::gfx::{console, Color};
+    let color = match level {
+        Level::Error => Color::RED,
+        Level::Warn => Color::rgb(0xFF, 0xC0, 0),
+        _ => Color::GRAY,
+    };
+    console::write(text, color);
+}
+
+/// Fixed-size formatting buffer; silently truncates.
+struct LineBuf {
+    buf: [u8; MAX_RECORD],
+    len: usize,
+}
+
+impl LineBuf {
+    fn new() -> Self {
+        Self { buf: [0; MAX_RECORD], len: 0 }
+    }
+
+    fn as_str(&self) -> &str {
+        // Truncation may split a UTF-8 sequence; keep the valid prefix
+        match core::str::from_utf8(&self.buf[..self.len]) {
+            Ok(s) => s,
+            Err(e) => unsafe { core::str::from_utf8_unchecked(&self.buf[..e.valid_up_to()]) },
+        }
+    }
+}
+
+impl Write for LineBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(MAX_RECORD - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// True if any sink would take a record of `level`.
+pub fn enabled(level: Level) -> bool {
+    SINKS.iter().any(|s| s.enabled(level))
+}
+
+/// Formats one record and writes it to every admitting sink.
+pub fn log(level: Level, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let mut line = LineBuf::new();
+    let _ = write!(line, "[{}] {}", level.name(), args);
+    if line.len == MAX_RECORD {
+        line.len -= 1;
+    }
+    let _ = line.write_str("\n");
+
+    early::record(level, line.as_str());
+    for sink in &SINKS {
+        if sink.ready.load(Ordering::Acquire) && sink.enabled(level) && sink.admit() {
+            (sink.write)(level, line.as_str());
+        }
+    }
+}
+
+/// Marks the sink `name` as usable and replays the early buffer into it.
+///
+/// Called by the sink's driver at the end of its init.
+pub fn mark_ready(name: &str) {
+    let Some(sink) = SINKS.iter().find(|s| s.name == name) else {
+        return;
+    };
+    if early::is_open() {
+        let lost = early::replay(|level, text| {
+            if sink.enabled(level) {
+                (sink.write)(level, text);
+            }
+        });
+        if lost > 0 {
+            let mut line = LineBuf::new();
+            let _ = writeln!(line, "[warn] log: {} early records lost", lost);
+            (sink.write)(Level::Warn, line.as_str());
+        }
+    }
+    sink.ready.store(true, Ordering::Release);
+}
+
+/// Applies `loglevel`/`lograte` options. Call after cmdline init.
+pub fn init() {
+    let default = cmdline::get("loglevel").and_then(Level::parse);
+    for sink in &SINKS {
+        if let Some(level) = option(sink, "loglevel.").and_then(Level::parse).or(default) {
+            sink.max_level.store(level as u8, Ordering::Relaxed);
+        }
+        if let Some(rate) = option(sink, "lograte.").and_then(|v| v.parse().ok()) {
+            sink.rate.store(rate, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Value of `<prefix><sink name>` on the command line.
+fn option(sink: &Sink, prefix: &str) -> Option<&'static str> {
+    let mut key = LineBuf::new();
+    let _ = write!(key, "{}{}", prefix, sink.name);
+    cmdline::get(key.as_str())
+}
+
+// Not every level has a user yet
+#[allow(unused_macros)]
+macro_rules! log_error {
+    ($($arg:tt)*) => { $crate::kernel::log::log($crate::kernel::log::Level::Error, format_args!($($arg)*)) };
+}
+#[allow(unused_macros)]
+macro_rules! log_warn {
+    ($($arg:tt)*) => { $crate::kernel::log::log($crate::kernel::log::Level::Warn, format_args!($($arg)*)) };
+}
+#[allow(unused_macros)]
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::kernel::log::log($crate::kernel::log::Level::Info, format_args!($($arg)*)) };
+}
+#[allow(unused_macros)]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { $crate::kernel::log::log($crate::kernel::log::Level::Debug, format_args!($($arg)*)) };
+}
+#[allow(unused_macros)]
+macro_rules! log_trace {
+    ($($arg:tt)*) => { $crate::kernel::log::log($crate::kernel::log::Level::Trace, format_args!($($arg)*)) };
+}
+
+#[allow(unused_imports)]
+pub(crate) use {log_debug, log_error, log_info, log_trace, log_warn};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_parse_and_order() {
+        assert_eq!(Level::parse("warn"), Some(Level::Warn));
+        assert_eq!(Level::parse("verbose"), None);
+        assert!(Level::Error < Level::Trace);
+    }
+}
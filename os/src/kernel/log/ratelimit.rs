@@ -0,0 +1,103 @@
+//! Per-call-site rate limiting for log records
+//!
+//! `log_ratelimited!` gives each call site its own `RateLimit`: at most
+//! `burst` records per `interval` ticks get through. The rest are counted,
+//! and the next record that passes is preceded by "previous message
+//! repeated N times", so a tight faulting loop costs one line per interval
+//! instead of flooding the console.
+//!
+//! Windows are measured in timer ticks; before the tick runs, a call site
+//! prints its first `burst` records and then stays quiet.
+
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use crate::arch::x86::{idt::storage::TICK_COUNT, pit::TICK_HZ};
+
+/// Default window: 5 seconds
+pub const DEFAULT_INTERVAL: u64 = 5 * TICK_HZ as u64;
+/// Default records per window
+pub const DEFAULT_BURST: u32 = 10;
+
+/// Outcome of `RateLimit::check`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Print; `repeated` records were suppressed since the last one printed
+    Print { repeated: u32 },
+    Suppress,
+}
+
+pub struct RateLimit {
+    interval: u64,
+    burst: u32,
+    window_start: AtomicU64,
+    printed: AtomicU32,
+    missed: AtomicU32,
+}
+
+impl RateLimit {
+    pub const fn new(interval: u64, burst: u32) -> Self {
+        Self {
+            interval,
+            burst,
+            window_start: AtomicU64::new(0),
+            printed: AtomicU32::new(0),
+            missed: AtomicU32::new(0),
+        }
+    }
+
+    /// Charges one record at the current tick.
+    pub fn check(&self) -> Verdict {
+        self.check_at(TICK_COUNT.load(Ordering::Relaxed))
+    }
+
+    fn check_at(&self, now: u64) -> Verdict {
+        let start = self.window_start.load(Ordering::Relaxed);
+        if now.wrapping_sub(start) >= self.interval
+            && self
+                .window_start
+                .compare_exchange(start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.printed.store(0, Ordering::Relaxed);
+        }
+        if self.printed.fetch_add(1, Ordering::Relaxed) < self.burst {
+            Verdict::Print { repeated: self.missed.swap(0, Ordering::Relaxed) }
+        } else {
+            self.missed.fetch_add(1, Ordering::Relaxed);
+            Verdict::Suppress
+        }
+    }
+}
+
+/// Logs at most `DEFAULT_BURST` records per `DEFAULT_INTERVAL` from this
+/// call site: `log_ratelimited!(Level::Warn, "fmt", args..)`.
+macro_rules! log_ratelimited {
+    ($level:expr, $($arg:tt)*) => {{
+        use $crate::kernel::log::ratelimit::{RateLimit, Verdict, DEFAULT_BURST, DEFAULT_INTERVAL};
+        static LIMIT: RateLimit = RateLimit::new(DEFAULT_INTERVAL, DEFAULT_BURST);
+        if let Verdict::Print { repeated } = LIMIT.check() {
+            if repeated > 0 {
+                $crate::kernel::log::log($level, format_args!("previous message repeated {} times", repeated));
+            }
+            $crate::kernel::log::log($level, format_args!($($arg)*));
+        }
+    }};
+}
+
+pub(crate) use log_ratelimited;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_report() {
+        let limit = RateLimit::new(100, 2);
+        assert_eq!(limit.check_at(0), Verdict::Print { repeated: 0 });
+        assert_eq!(limit.check_at(1), Verdict::Print { repeated: 0 });
+        assert_eq!(limit.check_at(2), Verdict::Suppress);
+        assert_eq!(limit.check_at(3), Verdict::Suppress);
+        // New window: first record carries the suppressed count
+        assert_eq!(limit.check_at(100), Verdict::Print { repeated: 2 });
+        assert_eq!(limit.check_at(101), Verdict::Print { repeated: 0 });
+    }
+}
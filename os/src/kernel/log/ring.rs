@@ -0,0 +1,70 @@
+//! In-memory log sink
+//!
+//! Keeps the last `RING_SIZE` bytes of log output; older text is
+//! overwritten. Records are stored formatted, newline-terminated.
+
+use crate::sync::RwLock;
+use super::Level;
+
+pub const RING_SIZE: usize = 16 * 1024;
+
+struct Ring {
+    buf: [u8; RING_SIZE],
+    /// Total bytes ever written; `head % RING_SIZE` is the next write
+    head: usize,
+}
+
+impl Ring {
+    const fn new() -> Self {
+        Self { buf: [0; RING_SIZE], head: 0 }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.buf[self.head % RING_SIZE] = b;
+            self.head += 1;
+        }
+    }
+
+    /// Copies the newest `out.len()` bytes (or fewer) in write order.
+    fn copy_latest(&self, out: &mut [u8]) -> usize {
+        let len = out.len().min(self.head).min(RING_SIZE);
+        let start = self.head - len;
+        for (i, byte) in out[..len].iter_mut().enumerate() {
+            *byte = self.buf[(start + i) % RING_SIZE];
+        }
+        len
+    }
+}
+
+static RING: RwLock<Ring> = RwLock::new(Ring::new());
+
+/// Sink write function.
+pub fn write(_level: Level, text: &str) {
+    RING.write_irqsave().push(text.as_bytes());
+}
+
+/// Copies the newest log text into `out`; returns the byte count.
+pub fn copy_latest(out: &mut [u8]) -> usize {
+    RING.read_irqsave().copy_latest(out)
+}
+
+/// Bytes written since boot (including overwritten ones).
+pub fn total_written() -> usize {
+    RING.read_irqsave().head
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wraparound_keeps_newest() {
+        let mut ring = Ring::new();
+        ring.push(&[b'a'; RING_SIZE - 2]);
+        ring.push(b"bcde");
+        let mut out = [0u8; 5];
+        assert_eq!(ring.copy_latest(&mut out), 5);
+        assert_eq!(&out, b"abcde");
+    }
+}
@@ -0,0 +1,30 @@
+//! Build manifest
+//!
+//! Generated by `build.rs`: kernel version, build profile, compiler, git
+//! revision, enabled cargo features and the compiled-in drivers. Logged at
+//! boot so every serial log states the exact configuration it came from.
+//! There is no procfs yet to expose it as a file.
+
+include!(concat!(env!("OUT_DIR"), "/manifest.rs"));
+
+/// Logs the manifest.
+pub fn log() {
+    use crate::serial;
+
+    serial::write_fmt(format_args!(
+        "os {} ({}, {}, git {})\n",
+        VERSION, PROFILE, TARGET, GIT_REVISION
+    ));
+    serial::write_fmt(format_args!("  built with {}\n", RUSTC));
+    serial::write_str("  features:");
+    if FEATURES.is_empty() {
+        serial::write_str(" none");
+    }
+    for feature in FEATURES {
+        serial::write_fmt(format_args!(" {}", feature));
+    }
+    serial::write_str("\n  drivers:\n");
+    for (name, summary) in DRIVERS {
+        serial::write_fmt(format_args!("    {:<14} {}\n", name, summary));
+    }
+}
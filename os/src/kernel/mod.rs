@@ -1,4 +1,14 @@
 // kernel module
 pub mod init;   // kernel initialization
+pub mod cmdline;
+pub mod hal;
+pub mod irqsoff;
+pub mod log;
+pub mod manifest;
+pub mod power;
+pub mod preempt;
+pub mod resource;
+pub mod stack_protector;
+pub mod tick;
 
 pub use init::{early_init, kernel_loop};
@@ -0,0 +1,50 @@
+//! Orderly power-off
+//!
+//! `power_off` runs the shutdown stages in `STAGES` order with interrupts
+//! disabled, then asks the machine to turn off. Stages mirror boot in
+//! reverse: devices are stopped before the interrupt controller is
+//! masked. Filesystem sync, network quiesce and block cache flush stages
+//! go at the front of the table once those subsystems exist.
+//!
+//! Without an ACPI parser the power-off itself uses the fixed PM ports of
+//! QEMU (0x604) and Bochs/old QEMU (0xB004); on other machines the CPU is
+//! halted instead.
+
+use crate::arch::x86::{pic, port::PortRange};
+use crate::serial;
+
+/// One teardown step
+struct Stage {
+    name: &'static str,
+    run: fn(),
+}
+
+static STAGES: &[Stage] = &[
+    Stage { name: "drivers", run: crate::drivers::shutdown_all },
+    Stage { name: "interrupt controller", run: pic::mask_all },
+];
+
+/// PM1a control port and SLP_TYP|SLP_EN value of known emulators
+const EMULATOR_POWEROFF: [(u16, u16); 2] = [(0x604, 0x2000), (0xB004, 0x2000)];
+
+/// Tears down all subsystems and powers off; halts if that is not possible.
+pub fn power_off() -> ! {
+    x86_64::instructions::interrupts::disable();
+    serial::write_str("power: shutting down\n");
+
+    for stage in STAGES {
+        serial::write_fmt(format_args!("power: {}\n", stage.name));
+        (stage.run)();
+    }
+
+    for (port, value) in EMULATOR_POWEROFF {
+        if let Ok(pm) = PortRange::request(port, 2, "pm1a control") {
+            pm.write16(0, value);
+        }
+    }
+
+    serial::write_str("power: power-off not supported, halting\n");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
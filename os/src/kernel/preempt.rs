@@ -0,0 +1,129 @@
+//! Preemption and interrupt-nesting accounting
+//!
+//! One counter, split like Linux's `preempt_count`:
+//! - bits 0..8: `preempt_disable` depth
+//! - bits 8..16: hardware interrupt nesting depth
+//!
+//! Code may only schedule (or block) when the whole counter is zero.
+//! `might_sleep` is the check the scheduler and blocking primitives call.
+//!
+//! There is one CPU and no per-CPU area yet, so the counter is a single
+//! global; it moves into per-CPU data once SMP lands.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use crate::kernel::irqsoff;
+
+const PREEMPT_MASK: u32 = 0x0000_00FF;
+const HARDIRQ_SHIFT: u32 = 8;
+const HARDIRQ_OFFSET: u32 = 1 << HARDIRQ_SHIFT;
+const HARDIRQ_MASK: u32 = 0x0000_FF00;
+
+static PREEMPT_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// Deepest interrupt nesting observed since boot
+static MAX_IRQ_DEPTH: AtomicU32 = AtomicU32::new(0);
+
+/// Raw counter value (for diagnostics).
+pub fn count() -> u32 {
+    PREEMPT_COUNT.load(Ordering::Relaxed)
+}
+
+/// Current hardware interrupt nesting depth (0 = task context).
+pub fn irq_depth() -> u32 {
+    (count() & HARDIRQ_MASK) >> HARDIRQ_SHIFT
+}
+
+/// Deepest interrupt nesting seen so far.
+pub fn max_irq_depth() -> u32 {
+    MAX_IRQ_DEPTH.load(Ordering::Relaxed)
+}
+
+/// True inside any interrupt handler.
+pub fn in_interrupt() -> bool {
+    count() & HARDIRQ_MASK != 0
+}
+
+/// True if the current context may be preempted or block.
+pub fn preemptible() -> bool {
+    count() == 0 && x86_64::instructions::interrupts::are_enabled()
+}
+
+/// Disables preemption until the matching `preempt_enable`.
+#[track_caller]
+pub fn preempt_disable() {
+    let prev = PREEMPT_COUNT.fetch_add(1, Ordering::Relaxed);
+    debug_assert!(prev & PREEMPT_MASK != PREEMPT_MASK, "preempt_count overflow");
+    if prev & PREEMPT_MASK == 0 {
+        irqsoff::preempt_off_begin(core::panic::Location::caller());
+    }
+}
+
+/// Re-enables preemption.
+pub fn preempt_enable() {
+    let prev = PREEMPT_COUNT.fetch_sub(1, Ordering::Relaxed);
+    debug_assert!(prev & PREEMPT_MASK != 0, "preempt_enable without preempt_disable");
+    if prev & PREEMPT_MASK == 1 {
+        irqsoff::preempt_off_end();
+    }
+}
+
+/// RAII form of `preempt_disable`/`preempt_enable`
+pub struct PreemptGuard(());
+
+/// Disables preemption until the returned guard is dropped.
+#[track_caller]
+pub fn preempt_guard() -> PreemptGuard {
+    preempt_disable();
+    PreemptGuard(())
+}
+
+impl Drop for PreemptGuard {
+    fn drop(&mut self) {
+        preempt_enable();
+    }
+}
+
+/// Marks entry into an interrupt handler; the returned guard marks the exit.
+///
+/// Must be the first thing an IRQ handler does.
+pub fn irq_enter() -> IrqGuard {
+    let depth = ((PREEMPT_COUNT.fetch_add(HARDIRQ_OFFSET, Ordering::Relaxed) & HARDIRQ_MASK)
+        >> HARDIRQ_SHIFT)
+        + 1;
+    MAX_IRQ_DEPTH.fetch_max(depth, Ordering::Relaxed);
+    IrqGuard(())
+}
+
+/// Interrupt context marker returned by `irq_enter`
+pub struct IrqGuard(());
+
+impl IrqGuard {
+    /// Re-enables interrupts inside the handler so higher-priority vectors
+    /// can nest. Only for handlers that have no state shared with those
+    /// vectors; the controller still holds off equal and lower priorities
+    /// until this handler's EOI.
+    pub fn allow_nesting(&self) {
+        x86_64::instructions::interrupts::enable();
+    }
+}
+
+impl Drop for IrqGuard {
+    fn drop(&mut self) {
+        // Handlers return with iretq; close the window before leaving
+        x86_64::instructions::interrupts::disable();
+        PREEMPT_COUNT.fetch_sub(HARDIRQ_OFFSET, Ordering::Relaxed);
+    }
+}
+
+/// Panics if called where the current task may not sleep or be switched
+/// out (interrupt context, preemption disabled, interrupts off).
+#[track_caller]
+pub fn might_sleep() {
+    if !preemptible() {
+        panic!(
+            "might_sleep: not preemptible (preempt_count={:#x}, irq depth {})",
+            count(),
+            irq_depth()
+        );
+    }
+}
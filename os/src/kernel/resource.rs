@@ -0,0 +1,258 @@
+//! Resource tree: ownership of I/O port and physical memory ranges
+//!
+//! Drivers claim every range they touch before using it; an overlapping
+//! claim is rejected with the name of the current owner, so two drivers
+//! cannot silently drive the same ports. The equivalent of /proc/ioports
+//! and /proc/iomem is printed by `log_tree` until procfs exists.
+//!
+//! # Nesting
+//! - `CONTAINER` ranges (firmware memory map entries, buses) accept claims
+//!   that lie fully inside them; those become their children.
+//! - `SHARED` claims may overlap other `SHARED` claims of exactly the same
+//!   range (port 0x61 is used by both the PIT delay loop and the speaker).
+//!
+//! Any other overlap is a conflict. There is no heap, so the table is a
+//! fixed array of `MAX_RESOURCES` entries.
+
+use crate::sync::RwLock;
+use crate::kernel::log::log_warn;
+use crate::serial;
+
+/// Table capacity (both kinds together)
+pub const MAX_RESOURCES: usize = 128;
+
+/// Range may contain other claims
+pub const CONTAINER: u8 = 1 << 0;
+/// Range may be claimed again, identically, by other `SHARED` users
+pub const SHARED: u8 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// I/O port space
+    Io,
+    /// Physical memory (RAM, MMIO)
+    Mem,
+}
+
+/// One claimed range
+#[derive(Debug, Clone, Copy)]
+pub struct Resource {
+    pub name: &'static str,
+    pub kind: ResourceKind,
+    pub start: u64,
+    /// Inclusive
+    pub end: u64,
+    pub flags: u8,
+}
+
+impl Resource {
+    fn overlaps(&self, other: &Resource) -> bool {
+        self.kind == other.kind && self.start <= other.end && other.start <= self.end
+    }
+
+    fn contains(&self, other: &Resource) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// True if `other` may coexist with `self` (assumes they overlap).
+    fn compatible(&self, other: &Resource) -> bool {
+        (self.flags & CONTAINER != 0 && self.contains(other))
+            || (other.flags & CONTAINER != 0 && other.contains(self))
+            || (self.flags & other.flags & SHARED != 0
+                && self.start == other.start
+                && self.end == other.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceError {
+    /// Overlaps a range owned by `owner`
+    Conflict { owner: &'static str, start: u64, end: u64 },
+    /// Table has no free slot
+    TableFull,
+    /// Zero-length or overflowing range
+    InvalidRange,
+}
+
+/// Proof of a claim; pass to `release` to give the range back
+#[derive(Debug)]
+pub struct ResourceHandle(usize);
+
+struct Table {
+    slots: [Option<Resource>; MAX_RESOURCES],
+}
+
+static TABLE: RwLock<Table> = RwLock::new(Table {
+    slots: [None; MAX_RESOURCES],
+});
+
+/// Claims `[start, start + len)` of `kind` for `name`.
+///
+/// # Errors
+/// - `Conflict` with the first incompatible owner
+/// - `TableFull`, `InvalidRange`
+pub fn request(
+    kind: ResourceKind,
+    start: u64,
+    len: u64,
+    name: &'static str,
+    flags: u8,
+) -> Result<ResourceHandle, ResourceError> {
+    if len == 0 {
+        return Err(ResourceError::InvalidRange);
+    }
+    let end = start.checked_add(len - 1).ok_or(ResourceError::InvalidRange)?;
+    let new = Resource { name, kind, start, end, flags };
+
+    let mut table = TABLE.write_irqsave();
+    if let Some(owner) = table
+        .slots
+        .iter()
+        .flatten()
+        .find(|r| r.overlaps(&new) && !r.compatible(&new))
+    {
+        return Err(ResourceError::Conflict {
+            owner: owner.name,
+            start: owner.start,
+            end: owner.end,
+        });
+    }
+
+    let index = table
+        .slots
+        .iter()
+        .position(Option::is_none)
+        .ok_or(ResourceError::TableFull)?;
+    table.slots[index] = Some(new);
+    Ok(ResourceHandle(index))
+}
+
+/// Claims an exclusive I/O port range.
+pub fn request_ports(start: u16, len: u16, name: &'static str) -> Result<ResourceHandle, ResourceError> {
+    request(ResourceKind::Io, start as u64, len as u64, name, 0)
+}
+
+/// Claims an exclusive physical memory range.
+pub fn request_mem(start: u64, len: u64, name: &'static str) -> Result<ResourceHandle, ResourceError> {
+    request(ResourceKind::Mem, start, len, name, 0)
+}
+
+/// Gives a claimed range back.
+pub fn release(handle: ResourceHandle) {
+    TABLE.write_irqsave().slots[handle.0] = None;
+}
+
+/// Logs a failed claim in a uniform format.
+pub fn log_conflict(name: &str, error: ResourceError) {
+    match error {
+        ResourceError::Conflict { owner, start, end } => {
+            log_warn!("resource: {} conflicts with {} [{:#x}-{:#x}]", name, owner, start, end)
+        }
+        other => log_warn!("resource: {} not claimed ({:?})", name, other),
+    }
+}
+
+/// Calls `f(depth, resource)` for every claim of `kind`, parents before
+/// children, in address order.
+pub fn for_each(kind: ResourceKind, mut f: impl FnMut(usize, &Resource)) {
+    let table = TABLE.read_irqsave();
+    let mut order = [0usize; MAX_RESOURCES];
+    let mut count = 0;
+    for (i, slot) in table.slots.iter().enumerate() {
+        if matches!(slot, Some(r) if r.kind == kind) {
+            order[count] = i;
+            count += 1;
+        }
+    }
+
+    let get = |i: usize| table.slots[i].as_ref().unwrap();
+    // Parents sort first: same start, larger end
+    order[..count].sort_unstable_by_key(|&i| (get(i).start, u64::MAX - get(i).end));
+
+    for &i in &order[..count] {
+        let r = get(i);
+        let depth = order[..count]
+            .iter()
+            .filter(|&&j| j != i && get(j).flags & CONTAINER != 0 && get(j).contains(r))
+            .count();
+        f(depth, r);
+    }
+}
+
+/// Registers the bootloader memory map as top-level memory containers.
+///
+/// Adjacent regions of the same kind are merged to save table slots.
+pub fn register_memory_map(regions: &[bootloader_api::info::MemoryRegion]) {
+    use bootloader_api::info::MemoryRegionKind;
+
+    let name = |kind: MemoryRegionKind| match kind {
+        MemoryRegionKind::Usable => "System RAM",
+        MemoryRegionKind::Bootloader => "Bootloader",
+        _ => "Reserved",
+    };
+
+    let mut pending: Option<(u64, u64, &'static str)> = None;
+    for region in regions {
+        let kind = name(region.kind);
+        pending = match pending {
+            Some((start, end, k)) if k == kind && end == region.start => Some((start, region.end, k)),
+            Some((start, end, k)) => {
+                claim_container(start, end, k);
+                Some((region.start, region.end, kind))
+            }
+            None => Some((region.start, region.end, kind)),
+        };
+    }
+    if let Some((start, end, k)) = pending {
+        claim_container(start, end, k);
+    }
+}
+
+fn claim_container(start: u64, end: u64, name: &'static str) {
+    if let Err(e) = request(ResourceKind::Mem, start, end - start, name, CONTAINER) {
+        log_conflict(name, e);
+    }
+}
+
+/// Prints the port and memory trees (ioports / iomem).
+pub fn log_tree() {
+    for (kind, title, width) in [(ResourceKind::Io, "ioports", 4), (ResourceKind::Mem, "iomem", 12)] {
+        serial::write_fmt(format_args!("resource: {}\n", title));
+        for_each(kind, |depth, r| {
+            serial::write_fmt(format_args!(
+                "  {:indent$}{:0width$x}-{:0width$x} : {}\n",
+                "",
+                r.start,
+                r.end,
+                r.name,
+                indent = depth * 2,
+                width = width
+            ));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn io(start: u64, end: u64, flags: u8) -> Resource {
+        Resource { name: "t", kind: ResourceKind::Io, start, end, flags }
+    }
+
+    #[test]
+    fn test_compatibility() {
+        // Plain overlap
+        assert!(io(0x60, 0x64, 0).overlaps(&io(0x64, 0x64, 0)));
+        assert!(!io(0x60, 0x64, 0).compatible(&io(0x64, 0x64, 0)));
+        // Child inside container
+        assert!(io(0x0, 0xFF, CONTAINER).compatible(&io(0x20, 0x21, 0)));
+        assert!(!io(0x0, 0xFF, CONTAINER).compatible(&io(0xF0, 0x1F0, 0)));
+        // Identical shared claims only
+        assert!(io(0x61, 0x61, SHARED).compatible(&io(0x61, 0x61, SHARED)));
+        assert!(!io(0x61, 0x61, SHARED).compatible(&io(0x61, 0x61, 0)));
+        // Different kinds never overlap
+        let mem = Resource { kind: ResourceKind::Mem, ..io(0x60, 0x64, 0) };
+        assert!(!mem.overlaps(&io(0x60, 0x64, 0)));
+    }
+}
@@ -0,0 +1,33 @@
+//! Stack smashing protection (`-Z stack-protector=strong`)
+//!
+//! The compiler instruments functions that have local arrays or
+//! address-taken locals: the prologue stores `__stack_chk_guard` below the
+//! return address and the epilogue calls `__stack_chk_fail` if it changed.
+//! On `x86_64-unknown-none` LLVM reads the guard from this global rather
+//! than from `fs:0x28`, so no TLS setup is needed.
+//!
+//! The guard starts as a fixed value and `init` replaces it with a random
+//! one. Every instrumented function active across that change fails its
+//! check on return, so `init` is inlined into `kernel_main`, which never
+//! returns.
+
+/// Guard compared by every instrumented epilogue (fixed until `init`)
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __stack_chk_guard: u64 = 0x595E_9FBD_94FD_A700;
+
+/// Randomizes the guard from the entropy pool.
+///
+/// The low byte stays zero so an overflow through a C-string copy cannot
+/// reproduce the guard. Call only from `kernel_main`, after `rand::init`.
+#[inline(always)]
+pub fn init() {
+    let guard = crate::rand::next_u64() & !0xFF;
+    unsafe { (&raw mut __stack_chk_guard).write_volatile(guard) };
+}
+
+/// Called by an instrumented epilogue on guard mismatch
+#[no_mangle]
+pub extern "C" fn __stack_chk_fail() -> ! {
+    panic!("stack smashing detected");
+}
@@ -0,0 +1,41 @@
+//! Timer tick accounting
+//!
+//! Hardware-independent body of the timer IRQ, written against the HAL
+//! traits so it can be unit-tested with mocks.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::kernel::hal::{InterruptController, Uart};
+
+/// A heartbeat '.' is printed every this many ticks
+pub const TICKS_PER_DOT: u64 = 10;
+
+/// Counts one tick of `irq`, prints the heartbeat and acknowledges the IRQ.
+///
+/// Returns the new tick count.
+pub fn on_tick(ticks: &AtomicU64, irq: u8, ic: &impl InterruptController, out: &impl Uart) -> u64 {
+    let n = ticks.fetch_add(1, Ordering::SeqCst) + 1;
+    if n.is_multiple_of(TICKS_PER_DOT) {
+        out.write_byte(b'.');
+    }
+    ic.end_of_interrupt(irq);
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::hal::mock::{MockIrq, MockUart};
+
+    #[test]
+    fn test_heartbeat_and_eoi() {
+        let ticks = AtomicU64::new(0);
+        let (ic, out) = (MockIrq::default(), MockUart::new());
+        for _ in 0..2 * TICKS_PER_DOT + 1 {
+            on_tick(&ticks, 0, &ic, &out);
+        }
+        let (buf, len) = out.output();
+        assert_eq!(&buf[..len], b"..");
+        assert_eq!(ic.eoi_count.get() as u64, 2 * TICKS_PER_DOT + 1);
+        assert_eq!(ic.last_eoi.get(), Some(0));
+    }
+}
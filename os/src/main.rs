@@ -1,20 +1,39 @@
-#![no_std]
-#![no_main]
+// Unit tests build for the host (`make test`), with std and its harness
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 #![allow(dead_code)]
 #![feature(abi_x86_interrupt)]
 
 mod kernel;
 mod arch;
+mod drivers;
+mod gfx;
 mod long_mode;
 mod paging;
+mod rand;
 mod serial;
+mod sync;
+mod uaccess;
 
-use core::panic::PanicInfo;
+/// Map all physical memory so page tables can be walked through
+/// `physical_memory_offset` (user pointer checks, address space switches).
+pub static BOOTLOADER_CONFIG: bootloader_api::BootloaderConfig = {
+    let mut config = bootloader_api::BootloaderConfig::new_default();
+    config.mappings.physical_memory = Some(bootloader_api::config::Mapping::Dynamic);
+    config
+};
 
-bootloader_api::entry_point!(kernel_main);
+#[cfg(not(test))]
+bootloader_api::entry_point!(kernel_main, config = &BOOTLOADER_CONFIG);
 
 fn kernel_main(boot_info: &'static mut bootloader_api::BootInfo) -> ! {
-    match kernel::early_init(&*boot_info) {
+    serial::init();
+    // Seed the stack guard here: this frame never returns, so no check can
+    // see the guard change underneath it.
+    rand::init();
+    kernel::stack_protector::init();
+
+    match kernel::early_init(boot_info) {
         Ok(state) => kernel::kernel_loop(state),
         Err(_) => {
             serial::write_str("paging: init failed\n");
@@ -23,8 +42,9 @@ fn kernel_main(boot_info: &'static mut bootloader_api::BootInfo) -> ! {
     }
 }
 
+#[cfg(not(test))]
 #[panic_handler]
-fn panic(info: &PanicInfo) -> ! {
+fn panic(info: &core::panic::PanicInfo) -> ! {
     x86_64::instructions::interrupts::disable();
 
     crate::serial::write_str("KERNEL PANIC: ");
@@ -35,6 +55,11 @@ fn panic(info: &PanicInfo) -> ! {
             location.line()
         ));
     }
+    // Carries the details, e.g. the #CP reason and RIP
+    crate::serial::write_fmt(format_args!("{}\n", info.message()));
+
+    // Audible hint on headless machines without a serial console
+    crate::drivers::speaker::beep(880, 300);
 
     loop {
         x86_64::instructions::hlt();
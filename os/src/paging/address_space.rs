@@ -15,17 +15,17 @@
 //! - INVARIANT: AddressSpaceId::KERNEL is never destroyed
 //! - INVARIANT: Active address space is never destroyed
 
-use super::{mapper, EarlyFrameAllocator, PagingError, PagingResult};
+use super::{mapper, walk, EarlyFrameAllocator, PagingError, PagingResult};
 use x86_64::{
     registers::control::Cr3,
     structures::paging::{
-        FrameAllocator, OffsetPageTable, PageTable, PageTableFlags as Flags,
+        FrameAllocator, FrameDeallocator, OffsetPageTable, PageTable, PageTableFlags as Flags,
         PhysFrame, Size4KiB, PageSize,
     },
-    VirtAddr,
+    PhysAddr, VirtAddr,
 };
 use super::pt::PageTableRoot;
-use super::mapper::MapType;
+use super::mapper::{MapType, PageSizePolicy};
 
 /// Opaque identifier for an address space.
 ///
@@ -75,16 +75,50 @@ impl core::fmt::Display for AddressSpaceId {
 }
 
 /// Memory usage statistics for an address space
-#[derive(Debug, Clone, Copy, Default)]
+///
+/// Computed by walking the page tables, so it always matches what the
+/// hardware sees: unmaps are reflected and overlapping maps count once.
+/// Page counts are in 4 KiB units; a 2 MiB page counts as 512.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct MemoryStats {
-    /// Number of mapped pages (approximate)
+    /// Number of mapped pages
     pub mapped_pages: usize,
     
-    /// Number of user pages mapped
+    /// Pages reachable from user mode (USER at every level)
     pub user_pages: usize,
     
-    /// Number of kernel pages (shared)
+    /// Supervisor-only pages
     pub kernel_pages: usize,
+
+    /// Leaf entries that are 2 MiB or 1 GiB pages
+    pub huge_mappings: usize,
+
+    /// Frames holding the page tables themselves, root included
+    pub table_frames: usize,
+}
+
+impl MemoryStats {
+    /// Change from `earlier` to `self`.
+    pub fn delta_since(&self, earlier: &MemoryStats) -> MemoryStatsDelta {
+        let d = |now: usize, then: usize| now as isize - then as isize;
+        MemoryStatsDelta {
+            mapped_pages: d(self.mapped_pages, earlier.mapped_pages),
+            user_pages: d(self.user_pages, earlier.user_pages),
+            kernel_pages: d(self.kernel_pages, earlier.kernel_pages),
+            huge_mappings: d(self.huge_mappings, earlier.huge_mappings),
+            table_frames: d(self.table_frames, earlier.table_frames),
+        }
+    }
+}
+
+/// Signed difference between two `MemoryStats` samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStatsDelta {
+    pub mapped_pages: isize,
+    pub user_pages: isize,
+    pub kernel_pages: isize,
+    pub huge_mappings: isize,
+    pub table_frames: isize,
 }
 
 /// Address Space: isolated virtual memory context.
@@ -110,8 +144,8 @@ pub struct AddressSpace {
     /// Root page table
     pt_root: PageTableRoot,
     
-    /// Memory usage statistics
-    stats: MemoryStats,
+    /// Statistics at the last `sample_stats` call
+    last_sample: MemoryStats,
 }
 
 impl AddressSpace {
@@ -138,7 +172,7 @@ impl AddressSpace {
         Self {
             id,
             pt_root: PageTableRoot::new(root_frame, kernel_offset),
-            stats: MemoryStats::default(),
+            last_sample: MemoryStats::default(),
         }
     }
 
@@ -177,7 +211,11 @@ impl AddressSpace {
     /// TLB shootdown (Stage 2B+).
     #[inline]
     pub unsafe fn switch_to(&self) {
-        let (_, flags) = Cr3::read();
+        let (current, flags) = Cr3::read();
+        if current != self.pt_root.frame() {
+            // Keep branch predictions trained by the old space out of this one
+            crate::arch::x86::spec_ctrl::barrier_on_switch();
+        }
         Cr3::write(self.pt_root.frame(), flags);
     }
 
@@ -240,19 +278,14 @@ impl AddressSpace {
                 kernel_end - kernel_start,
                 Flags::PRESENT | Flags::WRITABLE | Flags::GLOBAL,
                 MapType::Identity,
+                PageSizePolicy::Prefer2MiB,
             )?;
         }
 
-        let kernel_pages = ((kernel_end - kernel_start) / Size4KiB::SIZE) as usize;
-
         Ok(AddressSpace {
             id,
             pt_root: PageTableRoot::new(root_frame, kernel_offset),
-            stats: MemoryStats {
-                mapped_pages: kernel_pages,
-                user_pages: 0,
-                kernel_pages,
-            },
+            last_sample: MemoryStats::default(),
         })
     }
 
@@ -287,9 +320,7 @@ impl AddressSpace {
         size: u64,
     ) -> PagingResult<()> {
         let mut mapper = self.pt_root.mapper();
-        
-        let page_count = ((size + Size4KiB::SIZE - 1) / Size4KiB::SIZE) as usize;
-        
+
         // SAFETY: Caller guarantees safety requirements
         unsafe {
             mapper::map_region(
@@ -299,13 +330,10 @@ impl AddressSpace {
                 size,
                 Flags::PRESENT | Flags::WRITABLE | Flags::USER_ACCESSIBLE,
                 MapType::Allocate,
+                PageSizePolicy::Only4KiB,
             )?;
         }
 
-        // Update statistics
-        self.stats.mapped_pages += page_count;
-        self.stats.user_pages += page_count;
-
         Ok(())
     }
 
@@ -334,9 +362,7 @@ impl AddressSpace {
         size: u64,
     ) -> PagingResult<()> {
         let mut mapper = self.pt_root.mapper();
-        
-        let page_count = ((size + Size4KiB::SIZE - 1) / Size4KiB::SIZE) as usize;
-        
+
         // SAFETY: Caller guarantees safety requirements
         unsafe {
             mapper::map_region(
@@ -346,20 +372,59 @@ impl AddressSpace {
                 size,
                 Flags::PRESENT | Flags::WRITABLE | Flags::GLOBAL,
                 MapType::Identity,
+                PageSizePolicy::Prefer2MiB,
             )?;
         }
 
-        // Update statistics
-        self.stats.mapped_pages += page_count;
-        self.stats.kernel_pages += page_count;
-
         Ok(())
     }
 
     /// Returns memory usage statistics for this address space.
-    #[inline]
+    ///
+    /// Walks the whole page table hierarchy; cost is proportional to the
+    /// number of page tables, not to the mapped size.
     pub fn stats(&self) -> MemoryStats {
-        self.stats
+        let mut stats = MemoryStats::default();
+        // SAFETY: read-only walk of tables owned by this address space
+        let info = unsafe {
+            walk::for_each_leaf(self.pt_root.frame(), self.pt_root.phys_offset(), |leaf| {
+                let pages = (leaf.size / Size4KiB::SIZE) as usize;
+                stats.mapped_pages += pages;
+                if leaf.flags.contains(Flags::USER_ACCESSIBLE) {
+                    stats.user_pages += pages;
+                } else {
+                    stats.kernel_pages += pages;
+                }
+                if leaf.size > Size4KiB::SIZE {
+                    stats.huge_mappings += 1;
+                }
+            })
+        };
+        stats.table_frames = info.table_frames;
+        stats
+    }
+
+    /// Takes a fresh `stats()` sample and returns the change since the
+    /// previous call (the first call reports everything as new).
+    pub fn sample_stats(&mut self) -> MemoryStatsDelta {
+        let now = self.stats();
+        let delta = now.delta_since(&self.last_sample);
+        self.last_sample = now;
+        delta
+    }
+
+    /// Translates a virtual address through this address space.
+    ///
+    /// Walks the page tables via the kernel physical offset, so it works
+    /// whether or not this space is active. Returns the physical address
+    /// (including the offset into the page) and the effective flags, or
+    /// `None` if the address is not mapped.
+    ///
+    /// Meant for drivers that hand buffer addresses to devices and for
+    /// fault diagnostics; the result is only stable while the mapping is.
+    pub fn translate(&self, addr: VirtAddr) -> Option<(PhysAddr, Flags)> {
+        // SAFETY: read-only walk of tables owned by this address space
+        unsafe { walk::translate(self.pt_root.frame(), self.pt_root.phys_offset(), addr) }
     }
 
     /// Returns a Mapper for this AddressSpace.
@@ -383,7 +448,18 @@ impl AddressSpace {
         self.pt_root.frame()
     }
 
-    /// Destroys this address space and deallocates its page tables.
+    /// Destroys this address space and returns its memory to the allocator.
+    ///
+    /// Walks the user half of the hierarchy (PML4 entries 0..256) and frees
+    /// every page table below it together with the user frames it maps,
+    /// then frees the PML4 itself. The kernel half is skipped: its tables
+    /// are shared with or owned by the kernel address space.
+    ///
+    /// Only 4 KiB leaves marked `OWNED_FRAME` are freed as user frames;
+    /// those are the frames `map_user_region` allocated, including ones
+    /// `protect_region` has since made kernel-only. Identity mappings in
+    /// the user half (kernel or device memory) and huge pages are unmapped
+    /// but their frames are left alone.
     ///
     /// # Safety Requirements (CRITICAL)
     /// Caller must ensure:
@@ -391,30 +467,15 @@ impl AddressSpace {
     /// - This is NOT the kernel address space (ID 0)
     /// - No other references to this address space exist
     /// - No threads are using this address space
-    ///
-    /// # Implementation Note
-    /// Stage 2A: Deallocates PML4 frame only (page tables leak - will fix in Stage 2B)
-    /// Stage 2B+: Will recursively deallocate all page table levels
-    ///
-    /// # Memory Leak Warning
-    /// Currently (Stage 2A), this only frees the PML4 frame. All lower-level
-    /// page tables (PDPT, PD, PT) and the mapped frames leak. This is
-    /// acceptable for Stage 2A but MUST be fixed before production use.
-    ///
-    /// TODO(Stage 2B): Implement recursive page table deallocation:
-    /// 1. Walk PML4 entries
-    /// 2. For each valid entry, walk PDPT
-    /// 3. For each valid PDPT entry, walk PD
-    /// 4. For each valid PD entry, walk PT  
-    /// 5. Deallocate PT frames (skip kernel mappings)
-    /// 6. Deallocate PD frames
-    /// 7. Deallocate PDPT frames
-    /// 8. Deallocate PML4 frame
+    /// - Every table and user frame was allocated from `frame_allocator`
     ///
     /// # Panics
     /// - Panics if attempting to destroy kernel address space
     /// - Panics if attempting to destroy currently active address space (debug only)
-    pub unsafe fn destroy(self, frame_allocator: &mut EarlyFrameAllocator) {
+    pub unsafe fn destroy(
+        self,
+        frame_allocator: &mut impl FrameDeallocator<Size4KiB>,
+    ) -> TeardownStats {
         // SAFETY CHECK 1: Never destroy kernel address space
         if self.id == AddressSpaceId::KERNEL {
             panic!("Attempted to destroy kernel address space - this is forbidden");
@@ -432,25 +493,73 @@ impl AddressSpace {
             }
         }
 
-        // Stage 2A: Just deallocate the PML4 frame
-        // This leaks all page tables and mapped memory - acceptable for now
-        
-        // Get the PML4 frame before self is consumed
-        let _pml4_frame = self.pt_root.frame();
-        
-        // TODO(Stage 2B): Recursive deallocation here
-        // For now, we intentionally leak to avoid complex unsafe code
-        // This will be fixed when we implement proper page table walking
-        
-        // Note: We don't actually deallocate the frame yet because
-        // EarlyFrameAllocator doesn't support deallocation
-        // This will be added in Stage 2B with a proper frame allocator
-        
-        let _ = frame_allocator; // Silence unused warning
-        
-        // Drop self, releasing the PageTableRoot
-        core::mem::drop(self);
+        // SAFETY: caller guarantees exclusive ownership of the tables
+        unsafe { free_user_half(self.pt_root.frame(), self.pt_root.phys_offset(), frame_allocator) }
+    }
+}
+
+/// Frames returned to the allocator by `AddressSpace::destroy`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TeardownStats {
+    /// Page table frames, root included
+    pub table_frames: usize,
+    /// User data frames
+    pub user_frames: usize,
+}
+
+/// First PML4 index of the kernel half
+const KERNEL_PML4_START: usize = 256;
+
+/// Frees the user half below `root`, then `root` itself.
+unsafe fn free_user_half(
+    root: PhysFrame<Size4KiB>,
+    phys_offset: VirtAddr,
+    dealloc: &mut impl FrameDeallocator<Size4KiB>,
+) -> TeardownStats {
+    let mut stats = TeardownStats::default();
+    let pml4 = &mut *((phys_offset.as_u64() + root.start_address().as_u64()) as *mut PageTable);
+
+    for entry in pml4.iter_mut().take(KERNEL_PML4_START) {
+        if entry.flags().contains(Flags::PRESENT) {
+            free_table(entry.addr(), phys_offset, 2, dealloc, &mut stats);
+        }
+        entry.set_unused();
+    }
+
+    dealloc.deallocate_frame(root);
+    stats.table_frames += 1;
+    stats
+}
+
+/// Frees the table at `table_phys` (`level` 2 = PDPT .. 0 = PT), every
+/// table below it and the leaf frames it owns.
+unsafe fn free_table(
+    table_phys: PhysAddr,
+    phys_offset: VirtAddr,
+    level: usize,
+    dealloc: &mut impl FrameDeallocator<Size4KiB>,
+    stats: &mut TeardownStats,
+) {
+    let table = &mut *((phys_offset.as_u64() + table_phys.as_u64()) as *mut PageTable);
+
+    for entry in table.iter_mut() {
+        let flags = entry.flags();
+        if !flags.contains(Flags::PRESENT) {
+            continue;
+        }
+        if level == 0 {
+            if flags.contains(mapper::OWNED_FRAME) {
+                dealloc.deallocate_frame(PhysFrame::containing_address(entry.addr()));
+                stats.user_frames += 1;
+            }
+        } else if !flags.contains(Flags::HUGE_PAGE) {
+            free_table(entry.addr(), phys_offset, level - 1, dealloc, stats);
+        }
+        entry.set_unused();
     }
+
+    dealloc.deallocate_frame(PhysFrame::containing_address(table_phys));
+    stats.table_frames += 1;
 }
 
 // Future: Stage 2B+ will add Drop implementation for automatic cleanup
@@ -466,7 +575,7 @@ impl core::fmt::Debug for AddressSpace {
         f.debug_struct("AddressSpace")
             .field("id", &self.id)
             .field("pml4_frame", &self.pt_root.frame())
-            .field("stats", &self.stats)
+            .field("last_sample", &self.last_sample)
             .field("is_active", &self.is_active())
             .finish()
     }
@@ -488,4 +597,77 @@ mod tests {
     fn test_address_space_id_zero_panics() {
         let _ = AddressSpaceId::new(0);
     }
+
+    /// Records freed frames
+    #[derive(Default)]
+    struct Recorder {
+        freed: [u64; 8],
+        len: usize,
+    }
+
+    impl FrameDeallocator<Size4KiB> for Recorder {
+        unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+            self.freed[self.len] = frame.start_address().as_u64();
+            self.len += 1;
+        }
+    }
+
+    #[repr(C, align(4096))]
+    struct Frames([PageTable; 6]);
+
+    #[test]
+    fn test_teardown_frees_user_half_only() {
+        // PML4 -> PDPT -> PD -> PT -> {user frame, supervisor frame};
+        // PML4[256] points into the kernel half. Physical = virtual here.
+        let mut frames = Frames([const { PageTable::new() }; 6]);
+        let addr = |frames: &Frames, i: usize| PhysAddr::new(&frames.0[i] as *const _ as u64);
+        let table = Flags::PRESENT | Flags::WRITABLE | Flags::USER_ACCESSIBLE;
+        let links = [(0, 0, 1, table), (1, 0, 2, table), (2, 0, 3, table),
+                     (3, 0, 4, table | mapper::OWNED_FRAME),
+                     (3, 1, 5, Flags::PRESENT), (0, 256, 5, Flags::PRESENT)];
+        for (from, index, to, flags) in links {
+            let to = addr(&frames, to);
+            frames.0[from][index].set_addr(to, flags);
+        }
+        let root = PhysFrame::containing_address(addr(&frames, 0));
+
+        let mut rec = Recorder::default();
+        let stats = unsafe { free_user_half(root, VirtAddr::new(0), &mut rec) };
+
+        assert_eq!(stats, TeardownStats { table_frames: 4, user_frames: 1 });
+        let freed = &rec.freed[..rec.len];
+        // Five distinct frames in five slots: each freed exactly once
+        assert_eq!(freed.len(), 5);
+        for i in 0..5 {
+            assert!(freed.contains(&addr(&frames, i).as_u64()), "frame {} not freed", i);
+        }
+        assert!(!freed.contains(&addr(&frames, 5).as_u64()));
+        assert!(!frames.0[0][256].is_unused());
+    }
+
+    #[test]
+    fn test_teardown_frees_protected_frames() {
+        // PML4 -> PDPT -> PD -> PT -> user frame, as map_user_region leaves it
+        let mut frames = Frames([const { PageTable::new() }; 6]);
+        let addr = |frames: &Frames, i: usize| PhysAddr::new(&frames.0[i] as *const _ as u64);
+        let table = Flags::PRESENT | Flags::WRITABLE | Flags::USER_ACCESSIBLE;
+        for i in 0..3 {
+            let to = addr(&frames, i + 1);
+            frames.0[i][0].set_addr(to, table);
+        }
+        let to = addr(&frames, 4);
+        frames.0[3][0].set_addr(to, table | mapper::OWNED_FRAME);
+
+        // protect_region revokes user access to the page
+        let leaf = &mut frames.0[3][0];
+        leaf.set_flags(mapper::protected_flags(leaf.flags(), Flags::PRESENT));
+        assert!(!leaf.flags().contains(Flags::USER_ACCESSIBLE));
+
+        let root = PhysFrame::containing_address(addr(&frames, 0));
+        let mut rec = Recorder::default();
+        let stats = unsafe { free_user_half(root, VirtAddr::new(0), &mut rec) };
+
+        assert_eq!(stats, TeardownStats { table_frames: 4, user_frames: 1 });
+        assert!(rec.freed[..rec.len].contains(&addr(&frames, 4).as_u64()));
+    }
 }
@@ -36,6 +36,12 @@ pub enum PagingError {
         addr: VirtAddr,
     },
 
+    /// Page has no mapping to remove
+    NotMapped {
+        /// The page that was expected to be mapped
+        page: Page<Size4KiB>,
+    },
+
     /// Page is already mapped to a frame
     ///
     /// Indicates attempt to map a page that already has a valid mapping.
@@ -114,6 +120,7 @@ impl PagingError {
             Self::KernelAddressInUserSpace { .. } => {
                 "attempted to map kernel address in user space"
             }
+            Self::NotMapped { .. } => "page is not mapped",
             Self::AlreadyMapped { .. } => "page is already mapped",
             Self::Misaligned { .. } => "address is not properly aligned",
             Self::SizeOverflow { .. } => "size calculation overflow",
@@ -140,7 +147,7 @@ impl core::fmt::Display for PagingError {
                     addr.as_u64()
                 )
             }
-            Self::AlreadyMapped { page } => {
+            Self::AlreadyMapped { page } | Self::NotMapped { page } => {
                 write!(
                     f,
                     "{}: page at 0x{:x}",
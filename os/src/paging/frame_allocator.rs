@@ -4,15 +4,21 @@
 //! Excludes reserved regions (BIOS, kernel, bootloader).
 //!
 //! # Memory Safety
-//! - Never allocates the same frame twice
+//! - Never hands out a frame that is still allocated
 //! - Respects memory region types from bootloader
 //! - Maintains allocation watermarks for reliability
+//!
+//! # Recycling
+//! Freed frames go on an intrusive free list: each free frame stores the
+//! physical address of the next one in its first 8 bytes, reached through
+//! the physical memory offset. Allocation prefers the free list and zeroes
+//! recycled frames so no data leaks between address spaces.
 
 use bootloader_api::info::MemoryRegionKind;
 use x86_64::{
     addr::{align_down, align_up},
-    structures::paging::{FrameAllocator, PageSize, PhysFrame, Size4KiB},
-    PhysAddr,
+    structures::paging::{FrameAllocator, FrameDeallocator, PageSize, PhysFrame, Size4KiB},
+    PhysAddr, VirtAddr,
 };
 
 /// Maximum number of usable memory ranges we track
@@ -72,7 +78,7 @@ pub struct AllocatorStats {
 /// - Non-usable memory regions (reserved, ACPI, etc.)
 ///
 /// # Invariants
-/// - INVARIANT: Never allocates same frame twice
+/// - INVARIANT: Never hands out a frame that is still allocated
 /// - INVARIANT: All allocated frames are page-aligned
 /// - INVARIANT: Ranges never overlap
 /// - INVARIANT: Each range [start, end) has start < end
@@ -91,6 +97,15 @@ pub struct EarlyFrameAllocator {
     
     /// Initial total memory (for statistics)
     initial_total: u64,
+
+    /// Physical address of the first recycled frame, 0 if none
+    free_head: u64,
+
+    /// Number of frames on the free list
+    free_count: u64,
+
+    /// Where physical memory is mapped, for the free list links
+    phys_offset: VirtAddr,
 }
 
 impl EarlyFrameAllocator {
@@ -106,10 +121,12 @@ impl EarlyFrameAllocator {
     /// * `memory_regions` - Memory map from bootloader
     /// * `kernel_start` - Start of kernel in physical memory (currently unused, reserved for future)
     /// * `kernel_end` - End of kernel in physical memory (everything below is reserved)
+    /// * `phys_offset` - Virtual address where physical memory is mapped
     ///
     /// # Safety
     /// Caller must ensure:
     /// - `memory_regions` accurately describes physical RAM
+    /// - `phys_offset` maps all usable RAM (needed to recycle frames)
     /// - `kernel_end` covers all kernel and bootloader memory that must not be allocated
     /// - Bootloader has identity-mapped or higher-half mapped all memory
     ///
@@ -119,6 +136,7 @@ impl EarlyFrameAllocator {
         memory_regions: &[bootloader_api::info::MemoryRegion],
         _kernel_start: u64,
         kernel_end: u64,
+        phys_offset: VirtAddr,
     ) -> Self {
         let page_size = Size4KiB::SIZE;
         let mut ranges = [(0u64, 0u64); MAX_USABLE_RANGES];
@@ -171,6 +189,9 @@ impl EarlyFrameAllocator {
             len,
             next: 0,
             initial_total: total,
+            free_head: 0,
+            free_count: 0,
+            phys_offset,
         }
     }
 
@@ -192,10 +213,11 @@ impl EarlyFrameAllocator {
 
     /// Returns currently available memory in bytes (approximate).
     ///
-    /// This calculates available memory by summing up all remaining ranges.
-    /// It's approximate because fragmentation is not accounted for.
+    /// This calculates available memory by summing up all remaining ranges
+    /// and the free list. It's approximate because fragmentation is not
+    /// accounted for.
     pub fn available_memory(&self) -> u64 {
-        self.ranges[..self.len]
+        let untouched: u64 = self.ranges[..self.len]
             .iter()
             .map(|(start, end)| {
                 if end > start {
@@ -204,7 +226,8 @@ impl EarlyFrameAllocator {
                     0
                 }
             })
-            .sum()
+            .sum();
+        untouched + self.free_count * Size4KiB::SIZE
     }
 
     /// Returns allocated memory in bytes (approximate).
@@ -292,10 +315,23 @@ unsafe impl FrameAllocator<Size4KiB> for EarlyFrameAllocator {
     /// allocation index to avoid repeatedly scanning depleted ranges.
     ///
     /// # Invariants Maintained
-    /// - Never allocates the same frame twice
+    /// - Never hands out a frame that is still allocated
     /// - All returned frames are page-aligned
     /// - Frame is valid physical memory
     fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        if self.free_head != 0 {
+            let frame = PhysFrame::containing_address(PhysAddr::new(self.free_head));
+            let virt = (self.phys_offset.as_u64() + self.free_head) as *mut u64;
+            // SAFETY: frames on the free list are owned by the allocator
+            // and mapped through phys_offset (see `new`)
+            unsafe {
+                self.free_head = virt.read();
+                core::ptr::write_bytes(virt as *mut u8, 0, Size4KiB::SIZE as usize);
+            }
+            self.free_count -= 1;
+            return Some(frame);
+        }
+
         let n = self.len;
 
         // Try each range, starting from our hint
@@ -323,6 +359,20 @@ unsafe impl FrameAllocator<Size4KiB> for EarlyFrameAllocator {
     }
 }
 
+impl FrameDeallocator<Size4KiB> for EarlyFrameAllocator {
+    /// Puts `frame` on the free list for reuse.
+    ///
+    /// # Safety
+    /// The frame must have come from this allocator and must no longer be
+    /// mapped or referenced anywhere.
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        let addr = frame.start_address().as_u64();
+        ((self.phys_offset.as_u64() + addr) as *mut u64).write(self.free_head);
+        self.free_head = addr;
+        self.free_count += 1;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
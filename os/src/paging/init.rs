@@ -1,4 +1,4 @@
-use super::{AddressSpace, AddressSpaceId, EarlyFrameAllocator, PagingResult};
+use super::{map_phys_window_gib, phys_window, AddressSpace, AddressSpaceId, EarlyFrameAllocator, PagingResult};
 use bootloader_api::BootInfo;
 use crate::serial;
 use x86_64::{registers::control::Cr3, VirtAddr};
@@ -30,6 +30,7 @@ pub unsafe fn init(boot_info: &'static BootInfo) -> PagingResult<PagingState> {
         &boot_info.memory_regions,
         kernel_start,
         kernel_end,
+        kernel_offset,
     );
 
     let (current_pml4_frame, _) = Cr3::read();
@@ -40,6 +41,16 @@ pub unsafe fn init(boot_info: &'static BootInfo) -> PagingResult<PagingState> {
         kernel_offset,
     );
 
+    if kernel_offset.as_u64() != 0 {
+        remap_phys_window(boot_info, &kernel_space, kernel_offset);
+    }
+
+    let stats = kernel_space.stats();
+    serial::write_fmt(format_args!(
+        "Kernel space: {} pages mapped ({} huge mappings), {} table frames\n",
+        stats.mapped_pages, stats.huge_mappings, stats.table_frames
+    ));
+
     serial::write_str("Paging subsystem initialized\n");
     
     Ok(PagingState {
@@ -48,6 +59,26 @@ pub unsafe fn init(boot_info: &'static BootInfo) -> PagingResult<PagingState> {
     })    
 }
 
+/// Switches the RAM part of the physical memory window to 1 GiB pages
+/// where supported.
+///
+/// Failure is not fatal: the bootloader's window stays usable.
+unsafe fn remap_phys_window(
+    boot_info: &BootInfo,
+    kernel_space: &AddressSpace,
+    kernel_offset: VirtAddr,
+) {
+    if !phys_window::has_1gib_pages() {
+        serial::write_str("Physical window: no 1 GiB page support, keeping bootloader mapping\n");
+        return;
+    }
+
+    match map_phys_window_gib(kernel_space.root_frame(), kernel_offset, &boot_info.memory_regions) {
+        Ok(gib) => serial::write_fmt(format_args!("Physical window: {} x 1 GiB pages\n", gib)),
+        Err(e) => serial::write_fmt(format_args!("Physical window: keeping bootloader mapping ({})\n", e)),
+    }
+}
+
 /// Get physical memory offset from bootloader
 fn get_physical_memory_offset(boot_info: &BootInfo) -> VirtAddr {
     match boot_info.physical_memory_offset {
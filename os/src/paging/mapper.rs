@@ -11,7 +11,9 @@
 use super::{PagingError, PagingResult};
 use x86_64::{
     structures::paging::{
-        FrameAllocator, Mapper, Page, PageSize, PageTableFlags as Flags, PhysFrame, Size4KiB,
+        mapper::{FlagUpdateError, TranslateResult, UnmapError},
+        FrameAllocator, Mapper, Page, PageSize, PageTableFlags as Flags,
+        PhysFrame, Size2MiB, Size4KiB, Translate,
     },
     PhysAddr, VirtAddr,
 };
@@ -24,6 +26,11 @@ pub const KERNEL_SPACE_START: u64 = 0xFFFF_8000_0000_0000;
 /// Maximum user space address (exclusive)
 pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
 
+/// Software PTE bit: the frame was allocated for this mapping and is freed
+/// with it. Set by `map_region` for `MapType::Allocate` and kept across
+/// `protect_region`, so ownership does not depend on the permission bits.
+pub const OWNED_FRAME: Flags = Flags::BIT_9;
+
 /// Zeros a physical frame using proper virtual addressing.
 ///
 /// # Safety
@@ -54,6 +61,32 @@ pub enum MapType {
     Allocate,
 }
 
+/// Page sizes `map_region` may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSizePolicy {
+    /// 4 KiB pages only
+    Only4KiB,
+
+    /// 2 MiB pages wherever a whole aligned 2 MiB block fits in the range,
+    /// 4 KiB pages for the unaligned head and tail
+    ///
+    /// Only applies to `MapType::Identity`; allocated mappings need
+    /// physically contiguous 2 MiB frames, which the frame allocator cannot
+    /// provide, and stay on 4 KiB pages.
+    Prefer2MiB,
+}
+
+/// Size of the page `map_region` uses at `addr`, with `end` the
+/// (4 KiB aligned) end of the range.
+#[inline]
+fn page_size_at(addr: u64, end: u64, map_type: MapType, policy: PageSizePolicy) -> u64 {
+    let huge = policy == PageSizePolicy::Prefer2MiB
+        && map_type == MapType::Identity
+        && addr.is_multiple_of(Size2MiB::SIZE)
+        && end - addr >= Size2MiB::SIZE;
+    if huge { Size2MiB::SIZE } else { Size4KiB::SIZE }
+}
+
 /// Validates that an address is suitable for user space mapping.
 ///
 /// Returns `Err` if the address is in kernel space.
@@ -83,6 +116,9 @@ pub fn validate_alignment(addr: VirtAddr) -> PagingResult<()> {
 /// - Size is non-zero
 /// - Start + size doesn't overflow
 /// - Resulting range is valid
+///
+/// Returns the start and the last byte of the range (inclusive): the
+/// exclusive end of the top user page, `USER_SPACE_END`, is not canonical.
 pub fn validate_region(start: VirtAddr, size: u64) -> PagingResult<(VirtAddr, VirtAddr)> {
     // Size must be at least one page
     if size == 0 {
@@ -93,19 +129,20 @@ pub fn validate_region(start: VirtAddr, size: u64) -> PagingResult<(VirtAddr, Vi
     }
 
     // Check for overflow
-    let end_addr = start
+    let last_addr = start
         .as_u64()
-        .checked_add(size)
+        .checked_add(size - 1)
         .ok_or(PagingError::SizeOverflow { start, size })?;
 
-    let end = VirtAddr::new(end_addr);
-
     // Ensure the range doesn't span kernel/user boundary
-    if start.as_u64() < USER_SPACE_END && end.as_u64() > USER_SPACE_END {
+    if start.as_u64() < USER_SPACE_END && last_addr >= USER_SPACE_END {
         return Err(PagingError::InvalidRange);
     }
 
-    Ok((start, end))
+    // A range reaching into the non-canonical hole
+    let last = VirtAddr::try_new(last_addr).map_err(|_| PagingError::InvalidRange)?;
+
+    Ok((start, last))
 }
 
 /// Validates page table flags for user space mappings.
@@ -138,6 +175,56 @@ pub fn validate_kernel_flags(flags: Flags) -> PagingResult<()> {
     Ok(())
 }
 
+/// Validation shared by everything that installs mappings: alignment,
+/// region bounds, user/kernel flags for the range, and PRESENT.
+fn validate_mapping(virt_start: VirtAddr, size: u64, flags: Flags) -> PagingResult<()> {
+    validate_alignment(virt_start)?;
+    let (_start, _end) = validate_region(virt_start, size)?;
+
+    if virt_start.as_u64() < USER_SPACE_END {
+        validate_user_flags(flags)?;
+    } else {
+        validate_kernel_flags(flags)?;
+    }
+
+    // INVARIANT: flags must always include PRESENT
+    if !flags.contains(Flags::PRESENT) {
+        return Err(PagingError::InvalidFlags);
+    }
+
+    // Ownership is recorded by the mapper, never requested
+    if flags.contains(OWNED_FRAME) {
+        return Err(PagingError::InvalidFlags);
+    }
+
+    Ok(())
+}
+
+/// Validation for changing the flags of an existing mapping.
+///
+/// Like `validate_mapping`, except that a user-half range may drop
+/// USER_ACCESSIBLE (making it kernel-only); it still must not become
+/// GLOBAL, since it belongs to one address space. OWNED_FRAME is carried
+/// over by `protect_region` and cannot be passed in.
+fn validate_protection(virt_start: VirtAddr, size: u64, flags: Flags) -> PagingResult<()> {
+    validate_alignment(virt_start)?;
+    let (_start, _end) = validate_region(virt_start, size)?;
+
+    if virt_start.as_u64() < USER_SPACE_END {
+        if flags.contains(Flags::GLOBAL) {
+            return Err(PagingError::InvalidFlags);
+        }
+    } else {
+        validate_kernel_flags(flags)?;
+    }
+
+    if !flags.contains(Flags::PRESENT) || flags.contains(OWNED_FRAME) {
+        return Err(PagingError::InvalidFlags);
+    }
+
+    Ok(())
+}
+
 /// Maps a contiguous virtual range to physical memory.
 ///
 /// This is the core mapping function. It performs comprehensive validation
@@ -150,6 +237,7 @@ pub fn validate_kernel_flags(flags: Flags) -> PagingResult<()> {
 /// * `size` - Size in bytes (will be rounded up to page size)
 /// * `flags` - Page table flags for all pages in the range
 /// * `map_type` - How to map pages (identity or allocate)
+/// * `policy` - Which page sizes may be used
 ///
 /// # Safety
 /// - Can create invalid/aliasing mappings if misused
@@ -164,7 +252,8 @@ pub fn validate_kernel_flags(flags: Flags) -> PagingResult<()> {
 /// - Region is invalid or overflows
 /// - Flags are invalid for the address range
 /// - Frame allocation fails
-/// - Mapping operation fails
+/// - Mapping operation fails (including a 2 MiB page landing on a range
+///   that already has a 4 KiB page table)
 pub unsafe fn map_region<M>(
     mapper: &mut M,
     frame_allocator: &mut impl FrameAllocator<Size4KiB>,
@@ -172,49 +261,50 @@ pub unsafe fn map_region<M>(
     size: u64,
     flags: Flags,
     map_type: MapType,
+    policy: PageSizePolicy,
 ) -> PagingResult<()>
 where
-    M: Mapper<Size4KiB>,
+    M: Mapper<Size4KiB> + Mapper<Size2MiB>,
 {
-    // Validate alignment
-    validate_alignment(virt_start)?;
+    // Validate alignment, region and flags for the address range
+    validate_mapping(virt_start, size, flags)?;
 
-    // Validate region
-    let (_start, _end) = validate_region(virt_start, size)?;
-
-    // Validate flags based on address range
-    if virt_start.as_u64() < USER_SPACE_END {
-        // User space mapping
-        validate_user_flags(flags)?;
-    } else {
-        // Kernel space mapping
-        validate_kernel_flags(flags)?;
-    }
-
-    // INVARIANT: flags must always include PRESENT
-    if !flags.contains(Flags::PRESENT) {
-        return Err(PagingError::InvalidFlags);
-    }
-
-    // Calculate number of pages (round up)
-    let page_count = (size + Size4KiB::SIZE - 1) / Size4KiB::SIZE;
-    let start_page = Page::containing_address(virt_start);
+    // Round the end up to a whole 4 KiB page
+    let mut addr = virt_start.as_u64();
+    let end = addr + size.div_ceil(Size4KiB::SIZE) * Size4KiB::SIZE;
 
     // Map each page
-    for i in 0..page_count {
-        let page = start_page + i;
+    while addr < end {
+        if page_size_at(addr, end, map_type, policy) == Size2MiB::SIZE {
+            // Identity mapping only: VA == PA, both 2 MiB aligned
+            let page = Page::<Size2MiB>::containing_address(VirtAddr::new(addr));
+            let frame = PhysFrame::<Size2MiB>::containing_address(PhysAddr::new(addr));
+
+            // SAFETY: Caller guarantees this is safe
+            unsafe {
+                mapper
+                    .map_to(page, frame, flags, frame_allocator)
+                    .map_err(|_| PagingError::MapFailed)?
+                    .flush();
+            }
+            addr += Size2MiB::SIZE;
+            continue;
+        }
+
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
 
         // Determine physical frame
-        let frame = match map_type {
+        let (frame, page_flags) = match map_type {
             MapType::Identity => {
                 // Identity mapping: VA == PA
-                PhysFrame::containing_address(PhysAddr::new(page.start_address().as_u64()))
+                (PhysFrame::containing_address(PhysAddr::new(addr)), flags)
             }
             MapType::Allocate => {
-                // Allocate new frame
-                frame_allocator
+                // Allocate new frame, owned by the mapping
+                let frame = frame_allocator
                     .allocate_frame()
-                    .ok_or(PagingError::OutOfFrames)?
+                    .ok_or(PagingError::OutOfFrames)?;
+                (frame, flags | OWNED_FRAME)
             }
         };
 
@@ -222,10 +312,11 @@ where
         // SAFETY: Caller guarantees this is safe
         unsafe {
             mapper
-                .map_to(page, frame, flags, frame_allocator)
+                .map_to(page, frame, page_flags, frame_allocator)
                 .map_err(|_| PagingError::MapFailed)?
                 .flush(); // Flush TLB for this page
         }
+        addr += Size4KiB::SIZE;
     }
 
     Ok(())
@@ -252,20 +343,9 @@ where
     M: Mapper<Size4KiB>,
 {
     // Validate and map
-    validate_alignment(virt_start)?;
-    let (_start, _end) = validate_region(virt_start, size)?;
-
-    if virt_start.as_u64() < USER_SPACE_END {
-        validate_user_flags(flags)?;
-    } else {
-        validate_kernel_flags(flags)?;
-    }
-
-    if !flags.contains(Flags::PRESENT) {
-        return Err(PagingError::InvalidFlags);
-    }
+    validate_mapping(virt_start, size, flags)?;
 
-    let page_count = (size + Size4KiB::SIZE - 1) / Size4KiB::SIZE;
+    let page_count = size.div_ceil(Size4KiB::SIZE);
     let start_page = Page::containing_address(virt_start);
 
     for i in 0..page_count {
@@ -286,7 +366,7 @@ where
         // SAFETY: Caller guarantees this is safe
         unsafe {
             mapper
-                .map_to(page, frame, flags, frame_allocator)
+                .map_to(page, frame, flags | OWNED_FRAME, frame_allocator)
                 .map_err(|_| PagingError::MapFailed)?
                 .flush();
         }
@@ -295,7 +375,140 @@ where
     Ok(())
 }
 
-// Stage 2B+: Will add unmap_region, remap_region, protect_region, etc.
+/// Removes the mapping of a single page and flushes it from the TLB.
+///
+/// Returns the frame the page was mapped to. The frame is not freed;
+/// the caller decides whether it can be recycled.
+///
+/// # Safety
+/// - No live references may point into the page
+/// - Caller must not unmap memory the kernel itself is running on
+///
+/// # Errors
+/// - `NotMapped` if the page has no mapping
+/// - `MapFailed` if the page is part of a huge page or the entry is corrupt
+pub unsafe fn unmap_page<M>(mapper: &mut M, page: Page<Size4KiB>) -> PagingResult<PhysFrame<Size4KiB>>
+where
+    M: Mapper<Size4KiB>,
+{
+    match mapper.unmap(page) {
+        Ok((frame, flush)) => {
+            flush.flush();
+            Ok(frame)
+        }
+        Err(UnmapError::PageNotMapped) => Err(PagingError::NotMapped { page }),
+        Err(UnmapError::ParentEntryHugePage | UnmapError::InvalidFrameAddress(_)) => {
+            Err(PagingError::MapFailed)
+        }
+    }
+}
+
+/// Unmaps a contiguous virtual range.
+///
+/// Pages in the range that are not mapped are skipped, so a partially
+/// populated region can be torn down in one call. Each frame that was
+/// unmapped is passed to `freed`; pass `|_| {}` if the frames are not owned
+/// by the caller (e.g. identity-mapped device memory).
+///
+/// Returns the number of pages unmapped.
+///
+/// # Safety
+/// Same requirements as `unmap_page`, for every page in the range.
+///
+/// # Errors
+/// - `Misaligned`, `SizeTooSmall`, `SizeOverflow`, `InvalidRange` as for `map_region`
+/// - `MapFailed` if a page in the range is covered by a huge page; pages
+///   before it have already been unmapped
+pub unsafe fn unmap_region<M>(
+    mapper: &mut M,
+    virt_start: VirtAddr,
+    size: u64,
+    mut freed: impl FnMut(PhysFrame<Size4KiB>),
+) -> PagingResult<u64>
+where
+    M: Mapper<Size4KiB>,
+{
+    validate_alignment(virt_start)?;
+    let (_start, _end) = validate_region(virt_start, size)?;
+
+    let page_count = size.div_ceil(Size4KiB::SIZE);
+    let start_page = Page::containing_address(virt_start);
+    let mut unmapped = 0;
+
+    for i in 0..page_count {
+        // SAFETY: Caller guarantees this is safe
+        match unsafe { unmap_page(mapper, start_page + i) } {
+            Ok(frame) => {
+                freed(frame);
+                unmapped += 1;
+            }
+            Err(PagingError::NotMapped { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(unmapped)
+}
+
+/// Flags `protect_region` writes over a page currently mapped with `old`
+#[inline]
+pub(crate) fn protected_flags(old: Flags, new: Flags) -> Flags {
+    new | (old & OWNED_FRAME)
+}
+
+/// Replaces the flags of every page in an existing mapping.
+///
+/// Typical use is dropping WRITABLE (or USER_ACCESSIBLE) once a range has
+/// been filled. `new_flags` go through the checks of `map_region`, except
+/// that user-half pages may lose USER_ACCESSIBLE. Every page must be
+/// mapped; the frames are left untouched and each page keeps its
+/// OWNED_FRAME bit.
+///
+/// # Safety
+/// - Caller must ensure no code relies on the old permissions (e.g. a
+///   pending write into a range being made read-only)
+/// - Must not be called concurrently for overlapping regions
+///
+/// # Errors
+/// - Validation errors as for `map_region` (USER_ACCESSIBLE excepted)
+/// - `NotMapped` for the first page without a mapping
+/// - `MapFailed` if a page is covered by a huge page
+///
+/// Pages before the failing one have already been updated.
+pub unsafe fn protect_region<M>(
+    mapper: &mut M,
+    virt_start: VirtAddr,
+    size: u64,
+    new_flags: Flags,
+) -> PagingResult<()>
+where
+    M: Mapper<Size4KiB> + Translate,
+{
+    validate_protection(virt_start, size, new_flags)?;
+
+    let page_count = size.div_ceil(Size4KiB::SIZE);
+    let start_page = Page::containing_address(virt_start);
+
+    for i in 0..page_count {
+        let page = start_page + i;
+        // update_flags replaces the whole set; keep the ownership bit
+        let flags = match mapper.translate(page.start_address()) {
+            TranslateResult::Mapped { flags, .. } => protected_flags(flags, new_flags),
+            TranslateResult::NotMapped => return Err(PagingError::NotMapped { page }),
+            TranslateResult::InvalidFrameAddress(_) => return Err(PagingError::MapFailed),
+        };
+        // SAFETY: Caller guarantees this is safe
+        match unsafe { mapper.update_flags(page, flags) } {
+            Ok(flush) => flush.flush(),
+            Err(FlagUpdateError::PageNotMapped) => return Err(PagingError::NotMapped { page }),
+            Err(FlagUpdateError::ParentEntryHugePage) => return Err(PagingError::MapFailed),
+        }
+    }
+
+    Ok(())
+}
+
+// Stage 2B+: Will add remap_region, etc.
 
 #[cfg(test)]
 mod tests {
@@ -337,6 +550,11 @@ mod tests {
         // Overflow
         assert!(validate_region(VirtAddr::new(u64::MAX - 0x100), 0x1000).is_err());
 
+        // The top user page ends exactly at USER_SPACE_END
+        let top = VirtAddr::new(USER_SPACE_END - 0x1000);
+        assert_eq!(validate_region(top, 0x1000), Ok((top, VirtAddr::new(USER_SPACE_END - 1))));
+        assert!(validate_mapping(top, 0x1000, Flags::PRESENT | Flags::USER_ACCESSIBLE).is_ok());
+
         // Crossing kernel/user boundary
         assert!(validate_region(
             VirtAddr::new(USER_SPACE_END - 0x1000),
@@ -344,4 +562,50 @@ mod tests {
         )
         .is_err());
     }
+
+    #[test]
+    fn test_validate_mapping() {
+        let user = VirtAddr::new(0x40_0000);
+        let kernel = VirtAddr::new(KERNEL_SPACE_START);
+        let ro_user = Flags::PRESENT | Flags::USER_ACCESSIBLE;
+
+        assert!(validate_mapping(user, 0x2000, ro_user).is_ok());
+        assert!(validate_mapping(kernel, 0x2000, Flags::PRESENT).is_ok());
+
+        // New mappings must keep the user/kernel split and PRESENT
+        assert!(validate_mapping(user, 0x2000, Flags::PRESENT).is_err());
+        assert!(validate_mapping(kernel, 0x2000, ro_user).is_err());
+        assert!(validate_mapping(user, 0x2000, Flags::USER_ACCESSIBLE).is_err());
+        assert!(validate_mapping(user, 0x2000, ro_user | OWNED_FRAME).is_err());
+    }
+
+    #[test]
+    fn test_validate_protection() {
+        let user = VirtAddr::new(0x40_0000);
+        let kernel = VirtAddr::new(KERNEL_SPACE_START);
+
+        // Revoking user access to a user-half range is allowed
+        assert!(validate_protection(user, 0x2000, Flags::PRESENT).is_ok());
+        assert!(validate_protection(user, 0x2000, Flags::PRESENT | Flags::USER_ACCESSIBLE).is_ok());
+
+        assert!(validate_protection(user, 0x2000, Flags::PRESENT | Flags::GLOBAL).is_err());
+        assert!(validate_protection(kernel, 0x2000, Flags::PRESENT | Flags::USER_ACCESSIBLE).is_err());
+        assert!(validate_protection(user, 0x2000, Flags::USER_ACCESSIBLE).is_err());
+        assert!(validate_protection(user, 0x2000, Flags::PRESENT | OWNED_FRAME).is_err());
+    }
+
+    #[test]
+    fn test_page_size_at() {
+        const MIB: u64 = 1 << 20;
+        let at = |addr, end| page_size_at(addr, end, MapType::Identity, PageSizePolicy::Prefer2MiB);
+
+        // Unaligned head, aligned middle, short tail
+        assert_eq!(at(MIB, 7 * MIB), Size4KiB::SIZE);
+        assert_eq!(at(2 * MIB, 7 * MIB), Size2MiB::SIZE);
+        assert_eq!(at(6 * MIB, 7 * MIB), Size4KiB::SIZE);
+
+        // Allocated memory and the 4 KiB policy never get huge pages
+        assert_eq!(page_size_at(0, 4 * MIB, MapType::Allocate, PageSizePolicy::Prefer2MiB), Size4KiB::SIZE);
+        assert_eq!(page_size_at(0, 4 * MIB, MapType::Identity, PageSizePolicy::Only4KiB), Size4KiB::SIZE);
+    }
 }
@@ -16,13 +16,17 @@ mod error;
 mod frame_allocator;
 mod init;
 mod mapper;
+mod phys_window;
 mod pt;
+mod walk;
 
 // Public exports
 pub use address_space::{AddressSpace, AddressSpaceId};
 pub use error::{PagingError, PagingResult};
 pub use frame_allocator::EarlyFrameAllocator;
 pub use init::{init, PagingState};
+pub use mapper::USER_SPACE_END;
+pub use phys_window::map_phys_window_gib;
 
 // Internal utilities (not exported publicly)
 // pub use mapper::{map_region, zero_frame};
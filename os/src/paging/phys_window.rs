@@ -0,0 +1,171 @@
+//! Physical memory window with 1 GiB pages
+//!
+//! The bootloader maps all of physical memory at `physical_memory_offset`
+//! (NX, write-back) with 2 MiB pages. When the CPU supports 1 GiB pages
+//! (CPUID PDPE1GB), every GiB that the memory map reports as RAM
+//! throughout is switched to one PDPT entry, so most accesses through the
+//! offset (page table walks, frame zeroing, user copies) cost one TLB
+//! entry per GiB.
+//!
+//! GiBs touching anything else (the MMIO hole below 4 GiB with the LAPIC
+//! and framebuffer, firmware tables, holes) keep the bootloader's pages: a
+//! large page spanning several memory types is undefined. Nothing beyond
+//! the bootloader's window is mapped.
+//!
+//! Page tables the bootloader used below the replaced PDPT entries are not
+//! reclaimed: they sit in bootloader-reserved memory, not in frames the
+//! early frame allocator handed out, so they cannot go on its free list.
+
+use super::{PagingError, PagingResult};
+use bootloader_api::info::{MemoryRegion, MemoryRegionKind};
+use x86_64::{
+    instructions::{interrupts, tlb},
+    structures::paging::{
+        PageSize, PageTable, PageTableFlags as Flags, PhysFrame, Size1GiB, Size4KiB,
+    },
+    PhysAddr, VirtAddr,
+};
+
+/// CPUID 0x8000_0001 EDX: 1 GiB pages
+const CPUID_PDPE1GB: u32 = 1 << 26;
+
+/// Whether the CPU supports 1 GiB pages.
+pub fn has_1gib_pages() -> bool {
+    use core::arch::x86_64::__cpuid;
+
+    __cpuid(0x8000_0000).eax >= 0x8000_0001 && __cpuid(0x8000_0001).edx & CPUID_PDPE1GB != 0
+}
+
+/// True if `start..end` is covered by usable or bootloader-owned RAM.
+/// ACPI and other firmware-typed RAM is left out.
+fn is_ram(start: u64, end: u64, regions: &[MemoryRegion]) -> bool {
+    let mut pos = start;
+    while pos < end {
+        let next = regions.iter().find(|r| {
+            matches!(r.kind, MemoryRegionKind::Usable | MemoryRegionKind::Bootloader)
+                && r.start <= pos
+                && pos < r.end
+        });
+        match next {
+            Some(r) => pos = r.end,
+            None => return false,
+        }
+    }
+    true
+}
+
+/// PML4 and PDPT indices of a virtual address
+#[inline]
+fn slot(virt: u64) -> (usize, usize) {
+    (((virt >> 39) & 0x1FF) as usize, ((virt >> 30) & 0x1FF) as usize)
+}
+
+/// Switches the GiBs of the window at `phys_offset` that are RAM
+/// throughout (per `regions`) to 1 GiB pages in the tables at `root`.
+///
+/// Returns the number of 1 GiB pages in the window, or 0 if the CPU has no
+/// 1 GiB page support (the window is left as is).
+///
+/// Each entry is rewritten with interrupts off and the TLB is flushed at
+/// the end; until then either the old or the new translation may be used.
+/// Both map the same frames with the same attributes.
+///
+/// # Safety
+/// - `root` must be the active PML4 or one sharing its kernel half
+/// - `phys_offset` must be the bootloader's window and `regions` its
+///   memory map
+/// - No other CPU may walk or modify these tables concurrently
+///
+/// # Errors
+/// - `Misaligned` if `phys_offset` is not 1 GiB aligned
+/// - `InvalidRange` if a RAM GiB is outside kernel space or not mapped
+///   through a PDPT; GiBs before it have already been switched
+pub unsafe fn map_phys_window_gib(
+    root: PhysFrame<Size4KiB>,
+    phys_offset: VirtAddr,
+    regions: &[MemoryRegion],
+) -> PagingResult<u64> {
+    if !has_1gib_pages() {
+        return Ok(0);
+    }
+    if !phys_offset.is_aligned(Size1GiB::SIZE) {
+        return Err(PagingError::Misaligned { addr: phys_offset, required: Size1GiB::SIZE });
+    }
+
+    let offset = phys_offset.as_u64();
+    let table = |phys: PhysAddr| &mut *((offset + phys.as_u64()) as *mut PageTable);
+    let pml4 = table(root.start_address());
+    let gib_end = regions.iter().map(|r| r.end).max().unwrap_or(0) / Size1GiB::SIZE;
+    let huge = Flags::PRESENT | Flags::WRITABLE | Flags::HUGE_PAGE | Flags::NO_EXECUTE;
+    let mut installed = 0;
+
+    let result = interrupts::without_interrupts(|| {
+        for gib in 0..gib_end {
+            let phys = gib * Size1GiB::SIZE;
+            if !is_ram(phys, phys + Size1GiB::SIZE, regions) {
+                continue;
+            }
+            let virt = offset
+                .checked_add(phys)
+                .filter(|v| VirtAddr::try_new(*v).is_ok())
+                .ok_or(PagingError::InvalidRange)?;
+            let (pml4_index, pdpt_index) = slot(virt);
+
+            // Only replace what the bootloader mapped
+            let pml4_entry = &pml4[pml4_index];
+            if pml4_entry.is_unused() || pml4_entry.flags().contains(Flags::HUGE_PAGE) {
+                return Err(PagingError::InvalidRange);
+            }
+
+            let entry = &mut table(pml4_entry.addr())[pdpt_index];
+            if entry.is_unused() {
+                return Err(PagingError::InvalidRange);
+            }
+            if !entry.flags().contains(huge) || entry.addr().as_u64() != phys {
+                entry.set_addr(PhysAddr::new(phys), huge);
+            }
+            installed += 1;
+        }
+        Ok(())
+    });
+
+    // Drop the old 2 MiB entries, also after a partial switch
+    tlb::flush_all();
+    result.map(|()| installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot() {
+        assert_eq!(slot(0xFFFF_8000_0000_0000), (256, 0));
+        assert_eq!(slot(0xFFFF_8000_0000_0000 + 5 * Size1GiB::SIZE), (256, 5));
+        assert_eq!(slot(0xFFFF_8080_4000_0000), (257, 1));
+    }
+
+    #[test]
+    fn test_is_ram() {
+        const GIB: u64 = Size1GiB::SIZE;
+        let region = |start, end, kind| MemoryRegion { start, end, kind };
+        // Low RAM with the kernel in it, MMIO hole from 3 GiB, RAM above 4 GiB
+        let regions = [
+            region(0x1000, 0x10_0000, MemoryRegionKind::Usable),
+            region(0x10_0000, 0x40_0000, MemoryRegionKind::Bootloader),
+            region(0x40_0000, 3 * GIB, MemoryRegionKind::Usable),
+            region(4 * GIB, 6 * GIB + GIB / 2, MemoryRegionKind::Usable),
+            region(6 * GIB + GIB / 2, 7 * GIB, MemoryRegionKind::UnknownBios(2)),
+        ];
+
+        // First page missing from the map
+        assert!(!is_ram(0, GIB, &regions));
+        // Split across adjacent regions of both kinds
+        assert!(is_ram(0x1000, 2 * GIB, &regions));
+        assert!(is_ram(GIB, 2 * GIB, &regions));
+        assert!(!is_ram(3 * GIB, 4 * GIB, &regions));
+        assert!(is_ram(4 * GIB, 6 * GIB, &regions));
+        // Partly reserved
+        assert!(!is_ram(6 * GIB, 7 * GIB, &regions));
+    }
+}
@@ -23,4 +23,8 @@ impl PageTableRoot {
     pub fn frame(&self) -> PhysFrame<Size4KiB> {
         self.pml4
     }
+
+    pub fn phys_offset(&self) -> VirtAddr {
+        self.phys_offset
+    }
 }
@@ -0,0 +1,160 @@
+//! Read-only page table walking
+//!
+//! Visits every present leaf entry of a 4-level hierarchy, including 2 MiB
+//! and 1 GiB pages, and reports the flags in effect for it: WRITABLE and
+//! USER_ACCESSIBLE only if set at every level, NO_EXECUTE if set at any.
+//!
+//! Used for statistics and address translation that must match the
+//! hardware view rather than what callers remember having mapped.
+
+use x86_64::{
+    structures::paging::{PageTable, PageTableFlags as Flags, PhysFrame, Size4KiB},
+    PhysAddr, VirtAddr,
+};
+
+/// Bytes covered by one entry at each level (index 0 = PT)
+const LEVEL_SIZE: [u64; 4] = [1 << 12, 1 << 21, 1 << 30, 1 << 39];
+
+/// One present leaf mapping
+#[derive(Debug, Clone, Copy)]
+pub struct Leaf {
+    /// Canonical virtual start address
+    pub virt: VirtAddr,
+    /// Physical start address
+    pub phys: PhysAddr,
+    /// 4 KiB, 2 MiB or 1 GiB
+    pub size: u64,
+    /// Effective flags across all levels
+    pub flags: Flags,
+}
+
+/// Walk summary besides the leaves themselves
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WalkInfo {
+    /// Page table frames visited, including the root
+    pub table_frames: usize,
+}
+
+/// Sign-extends a 48-bit address to canonical form.
+#[inline]
+fn canonical(addr: u64) -> u64 {
+    (((addr << 16) as i64) >> 16) as u64
+}
+
+/// Flags in effect below an entry with `flags`, given those inherited from
+/// the levels above it.
+#[inline]
+fn effective_flags(inherited: Flags, flags: Flags) -> Flags {
+    let mut effective = flags;
+    effective.remove(!inherited & (Flags::WRITABLE | Flags::USER_ACCESSIBLE));
+    effective.insert(inherited & Flags::NO_EXECUTE);
+    effective
+}
+
+/// Calls `f` for every present leaf reachable from `root`.
+///
+/// # Safety
+/// - `root` must be a valid PML4 frame
+/// - `phys_offset` must map all physical memory holding its tables
+/// - The tables must not be modified during the walk
+pub unsafe fn for_each_leaf(
+    root: PhysFrame<Size4KiB>,
+    phys_offset: VirtAddr,
+    mut f: impl FnMut(&Leaf),
+) -> WalkInfo {
+    let mut info = WalkInfo::default();
+    let inherited = Flags::PRESENT | Flags::WRITABLE | Flags::USER_ACCESSIBLE;
+    walk_table(root.start_address(), phys_offset, 3, 0, inherited, &mut info, &mut f);
+    info
+}
+
+unsafe fn walk_table(
+    table_phys: PhysAddr,
+    phys_offset: VirtAddr,
+    level: usize,
+    base: u64,
+    inherited: Flags,
+    info: &mut WalkInfo,
+    f: &mut impl FnMut(&Leaf),
+) {
+    info.table_frames += 1;
+    let table = &*((phys_offset.as_u64() + table_phys.as_u64()) as *const PageTable);
+
+    for (index, entry) in table.iter().enumerate() {
+        let flags = entry.flags();
+        if !flags.contains(Flags::PRESENT) {
+            continue;
+        }
+
+        let virt = base + index as u64 * LEVEL_SIZE[level];
+        let effective = effective_flags(inherited, flags);
+
+        let is_leaf = level == 0 || (level < 3 && flags.contains(Flags::HUGE_PAGE));
+        if is_leaf {
+            f(&Leaf {
+                virt: VirtAddr::new_truncate(canonical(virt)),
+                phys: entry.addr(),
+                size: LEVEL_SIZE[level],
+                flags: effective,
+            });
+        } else {
+            walk_table(entry.addr(), phys_offset, level - 1, virt, effective, info, f);
+        }
+    }
+}
+
+/// Translates `virt` through the hierarchy at `root`.
+///
+/// Returns the physical address (page offset included, also inside 2 MiB
+/// and 1 GiB pages) and the effective flags of the leaf, or `None` if any
+/// level is not present.
+///
+/// # Safety
+/// Same as `for_each_leaf`.
+pub unsafe fn translate(
+    root: PhysFrame<Size4KiB>,
+    phys_offset: VirtAddr,
+    virt: VirtAddr,
+) -> Option<(PhysAddr, Flags)> {
+    let addr = virt.as_u64();
+    let mut table_phys = root.start_address();
+    let mut inherited = Flags::PRESENT | Flags::WRITABLE | Flags::USER_ACCESSIBLE;
+
+    for level in (0..4).rev() {
+        let table = &*((phys_offset.as_u64() + table_phys.as_u64()) as *const PageTable);
+        let entry = &table[((addr >> (12 + 9 * level)) & 0x1FF) as usize];
+        let flags = entry.flags();
+        if !flags.contains(Flags::PRESENT) {
+            return None;
+        }
+        inherited = effective_flags(inherited, flags);
+
+        if level == 0 || (level < 3 && flags.contains(Flags::HUGE_PAGE)) {
+            let offset = addr & (LEVEL_SIZE[level] - 1);
+            return Some((entry.addr() + offset, inherited));
+        }
+        table_phys = entry.addr();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical() {
+        assert_eq!(canonical(0x0000_7FFF_FFFF_F000), 0x0000_7FFF_FFFF_F000);
+        assert_eq!(canonical(0x0000_8000_0000_0000), 0xFFFF_8000_0000_0000);
+        assert_eq!(canonical(511 * LEVEL_SIZE[3]), 0xFFFF_FF80_0000_0000);
+    }
+
+    #[test]
+    fn test_effective_flags() {
+        let top = Flags::PRESENT | Flags::WRITABLE | Flags::USER_ACCESSIBLE;
+        let ro = Flags::PRESENT | Flags::USER_ACCESSIBLE | Flags::NO_EXECUTE;
+        // Read-only and NX above win over a writable, executable leaf
+        let below = effective_flags(top, ro);
+        assert_eq!(effective_flags(below, top), ro);
+    }
+}
@@ -0,0 +1,280 @@
+//! Kernel entropy pool and CSPRNG
+//!
+//! Collects entropy from the CPU and from interrupt timing and expands it
+//! with a ChaCha20-based generator. This is the single source of randomness
+//! for the kernel (KASLR/ASLR, stack guards, network sequence numbers) and
+//! backs `getrandom`, the intended kernel side of a future `sys_getrandom`
+//! (there is no syscall layer yet).
+//!
+//! # Sources
+//! - RDSEED / RDRAND when CPUID reports them
+//! - TSC jitter sampled at init
+//! - TSC at interrupt entry, mixed lock-free from IRQ handlers
+//!
+//! # Generator
+//! Fast key erasure: every request produces one ChaCha20 block per 64 output
+//! bytes plus one extra block that becomes the next key, so captured state
+//! cannot be used to recover earlier output.
+
+mod chacha;
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use x86_64::instructions::interrupts;
+use crate::serial;
+
+/// Interrupt events required before the IRQ samples are mixed into the key
+const RESEED_EVENTS: u64 = 64;
+
+/// Slots for pending interrupt samples; older ones are overwritten
+const IRQ_SLOTS: usize = 64;
+
+/// Interrupt samples mixed into the key before the pool counts as seeded.
+///
+/// Most samples are the periodic timer tick, whose TSC value is largely
+/// predictable; this assumes no more than 1/8 bit per sample, i.e. 256
+/// bits in total.
+const SEED_EVENTS: u64 = 2048;
+
+/// TSC jitter samples taken at init when no hardware RNG is available
+const JITTER_SAMPLES: usize = 256;
+
+/// Hardware seed words requested from RDSEED/RDRAND at init
+const HW_SEED_WORDS: usize = 8;
+
+/// `getrandom` flag: fail instead of waiting when the pool is not seeded
+pub const GRND_NONBLOCK: u32 = 1 << 0;
+
+/// Errors returned by `getrandom`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandError {
+    /// Pool has not collected enough entropy yet (EAGAIN)
+    NotSeeded,
+    /// Unknown flag bits (EINVAL)
+    InvalidFlags,
+}
+
+/// ChaCha20 generator state
+struct Generator {
+    key: [u32; 8],
+    /// Incremented per request so nonces never repeat under one key
+    requests: u64,
+}
+
+static mut GENERATOR: Generator = Generator {
+    key: [0; 8],
+    requests: 0,
+};
+
+/// Interrupt timing samples (written from IRQ context, one slot each)
+static IRQ_SAMPLES: [AtomicU64; IRQ_SLOTS] = [const { AtomicU64::new(0) }; IRQ_SLOTS];
+
+/// Interrupt events recorded since the last reseed
+static IRQ_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Interrupt samples mixed into the key so far, towards `SEED_EVENTS`
+static IRQ_MIXED: AtomicU64 = AtomicU64::new(0);
+
+/// Set once the generator has been keyed from a trusted amount of entropy
+static SEEDED: AtomicBool = AtomicBool::new(false);
+
+/// Hardware RNG instructions reported by CPUID
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HwSupport {
+    pub rdrand: bool,
+    pub rdseed: bool,
+}
+
+/// Queries CPUID for RDRAND (leaf 1 ECX[30]) and RDSEED (leaf 7 EBX[18]).
+pub fn hw_support() -> HwSupport {
+    use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+    let leaf1 = __cpuid(1);
+    let max_leaf = __cpuid(0).eax;
+    let rdseed = max_leaf >= 7 && (__cpuid_count(7, 0).ebx & (1 << 18)) != 0;
+
+    HwSupport {
+        rdrand: (leaf1.ecx & (1 << 30)) != 0,
+        rdseed,
+    }
+}
+
+#[inline(always)]
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Executes RDSEED, retrying a few times on underflow.
+fn rdseed64() -> Option<u64> {
+    for _ in 0..10 {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdseed {v}",
+                "setc {ok}",
+                v = out(reg) value,
+                ok = out(reg_byte) ok,
+                options(nomem, nostack)
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+        core::hint::spin_loop();
+    }
+    None
+}
+
+fn rdrand64() -> Option<u64> {
+    x86_64::instructions::random::RdRand::new().and_then(|r| r.get_u64())
+}
+
+/// Folds 64 bits of input into the generator key.
+///
+/// The key is XORed with the input and immediately re-derived through one
+/// ChaCha20 block, so partial knowledge of the input does not leak the key.
+///
+/// # Safety
+/// Caller must have exclusive access to `GENERATOR` (interrupts disabled).
+unsafe fn mix_into_key(gen: &mut Generator, input: u64, domain: u32) {
+    gen.key[0] ^= input as u32;
+    gen.key[1] ^= (input >> 32) as u32;
+    let nonce = [domain, gen.requests as u32, (gen.requests >> 32) as u32];
+    let out = chacha::block(&gen.key, u32::MAX, &nonce);
+    gen.key.copy_from_slice(&out[..8]);
+}
+
+/// Seeds the generator from hardware and TSC jitter. Call once at boot.
+pub fn init() {
+    let hw = hw_support();
+    let mut hw_words = 0usize;
+
+    interrupts::without_interrupts(|| unsafe {
+        let gen = &mut *(&raw mut GENERATOR);
+
+        for _ in 0..HW_SEED_WORDS {
+            let word = if hw.rdseed { rdseed64() } else { None }
+                .or_else(|| if hw.rdrand { rdrand64() } else { None });
+            if let Some(w) = word {
+                mix_into_key(gen, w, 1);
+                hw_words += 1;
+            }
+        }
+
+        // TSC jitter: timing of a short busy loop varies with cache and
+        // pipeline state. Weak on its own but always available.
+        let mut last = rdtsc();
+        for i in 0..JITTER_SAMPLES {
+            for _ in 0..(i % 7) {
+                core::hint::spin_loop();
+            }
+            let now = rdtsc();
+            mix_into_key(gen, now.wrapping_sub(last) ^ now.rotate_left(i as u32 % 64), 2);
+            last = now;
+        }
+    });
+
+    if hw_words == HW_SEED_WORDS {
+        SEEDED.store(true, Ordering::SeqCst);
+    }
+
+    serial::write_str("rand: RDRAND=");
+    serial::write_str(if hw.rdrand { "yes" } else { "no" });
+    serial::write_str(" RDSEED=");
+    serial::write_str(if hw.rdseed { "yes" } else { "no" });
+    serial::write_str(if is_seeded() { " (seeded)\n" } else { " (waiting for IRQ entropy)\n" });
+}
+
+/// Returns true once the generator is considered cryptographically seeded.
+#[inline]
+pub fn is_seeded() -> bool {
+    SEEDED.load(Ordering::Relaxed)
+}
+
+/// Records the timing of an interrupt. Safe to call from IRQ context.
+#[inline]
+pub fn add_interrupt_timing(vector: u8) {
+    let events = IRQ_EVENTS.fetch_add(1, Ordering::Relaxed);
+    IRQ_SAMPLES[events as usize % IRQ_SLOTS].store(rdtsc() ^ ((vector as u64) << 56), Ordering::Relaxed);
+}
+
+/// Mixes caller-provided data (device serials, MAC addresses) into the key.
+///
+/// Never credited as entropy: it only perturbs the state.
+pub fn add_device_data(data: u64) {
+    interrupts::without_interrupts(|| unsafe {
+        mix_into_key(&mut *(&raw mut GENERATOR), data, 3);
+    });
+}
+
+/// Mixes pending interrupt timings into the key once enough have arrived.
+///
+/// Each sample goes through its own `mix_into_key`, so one predictable
+/// sample cannot cancel another. Overwritten samples are not counted.
+///
+/// # Safety
+/// Caller must have exclusive access to `GENERATOR` (interrupts disabled).
+unsafe fn maybe_reseed(gen: &mut Generator) {
+    if IRQ_EVENTS.load(Ordering::Relaxed) < RESEED_EVENTS {
+        return;
+    }
+    let events = IRQ_EVENTS.swap(0, Ordering::Relaxed).min(IRQ_SLOTS as u64);
+    for slot in &IRQ_SAMPLES[..events as usize] {
+        mix_into_key(gen, slot.swap(0, Ordering::Relaxed), 4);
+    }
+    if IRQ_MIXED.fetch_add(events, Ordering::Relaxed) + events >= SEED_EVENTS {
+        SEEDED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Fills `buf` with output from the CSPRNG.
+///
+/// Does not check whether the pool is seeded; use `getrandom` for callers
+/// that must not consume output before then.
+pub fn fill_bytes(buf: &mut [u8]) {
+    interrupts::without_interrupts(|| unsafe {
+        let gen = &mut *(&raw mut GENERATOR);
+        maybe_reseed(gen);
+
+        gen.requests = gen.requests.wrapping_add(1);
+        let nonce = [0, gen.requests as u32, (gen.requests >> 32) as u32];
+
+        // Block 0 rekeys the generator; output starts at block 1.
+        let rekey = chacha::block(&gen.key, 0, &nonce);
+        let key = gen.key;
+        gen.key.copy_from_slice(&rekey[..8]);
+
+        for (i, chunk) in buf.chunks_mut(chacha::BLOCK_BYTES).enumerate() {
+            let block = chacha::block(&key, i as u32 + 1, &nonce);
+            for (j, byte) in chunk.iter_mut().enumerate() {
+                *byte = (block[j / 4] >> ((j % 4) * 8)) as u8;
+            }
+        }
+    });
+}
+
+/// Returns a random `u64` from the CSPRNG.
+pub fn next_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    fill_bytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+/// Fills `buf` with `getrandom(2)` semantics and returns bytes written.
+///
+/// Meant to back `sys_getrandom` once a syscall layer exists.
+///
+/// # Errors
+/// - `InvalidFlags` if flags other than `GRND_NONBLOCK` are set
+/// - `NotSeeded` if the pool is not seeded yet (there is no blocking wait
+///   until threads exist, so this is returned with or without the flag)
+pub fn getrandom(buf: &mut [u8], flags: u32) -> Result<usize, RandError> {
+    if flags & !GRND_NONBLOCK != 0 {
+        return Err(RandError::InvalidFlags);
+    }
+    if !is_seeded() {
+        return Err(RandError::NotSeeded);
+    }
+    fill_bytes(buf);
+    Ok(buf.len())
+}
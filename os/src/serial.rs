@@ -1,7 +1,11 @@
 //! Serial port (COM1 @ 0x3F8) for debug output. Stage 1 primary debug channel.
 
+use crate::arch::x86::port::PortRange;
+use crate::kernel::hal::Uart;
+
 const COM1: u16 = 0x3F8;
 
+const THR_OFF: u16 = 0;
 const LCR_OFF: u16 = 3;
 const LCR_8N1: u8 = 0x03;
 const MCR_OFF: u16 = 4;
@@ -9,32 +13,27 @@ const MCR_DTR_RTS: u8 = 0x03;
 const LSR_OFF: u16 = 5;
 const LSR_THRE: u8 = 0x20;
 
-unsafe fn outb(port: u16, value: u8) {
-    core::arch::asm!("out dx, al", in("dx") port, in("al") value, options(nostack, preserves_flags));
-}
-
-unsafe fn inb(port: u16) -> u8 {
-    let value: u8;
-    core::arch::asm!("in al, dx", in("dx") port, out("al") value, options(nostack, preserves_flags));
-    value
-}
+// Claimed in `init`
+static PORTS: PortRange = unsafe { PortRange::new(COM1, 8) };
 
 /// Initialize COM1 (8n1, no interrupts). Safe to call once at boot.
 pub fn init() {
-    unsafe {
-        outb(COM1 + LCR_OFF, LCR_8N1);
-        outb(COM1 + MCR_OFF, MCR_DTR_RTS);
+    if let Err(e) = PORTS.claim("serial", 0) {
+        crate::kernel::resource::log_conflict("serial", e);
     }
+    PORTS.write8(LCR_OFF, LCR_8N1);
+    PORTS.write8(MCR_OFF, MCR_DTR_RTS);
+    crate::kernel::log::mark_ready("serial");
 }
 
 fn is_transmit_empty() -> bool {
-    unsafe { (inb(COM1 + LSR_OFF) & LSR_THRE) != 0 }
+    (PORTS.read8(LSR_OFF) & LSR_THRE) != 0
 }
 
 /// Write one byte to serial. Blocks until THR empty. Call `init()` first.
 pub fn write_byte(b: u8) {
     while !is_transmit_empty() {}
-    unsafe { outb(COM1, b) }
+    PORTS.write8(THR_OFF, b)
 }
 
 /// Write a string to serial. Newlines not translated.
@@ -65,20 +64,39 @@ pub fn write_fmt(args: core::fmt::Arguments) {
 
 /// Write u64 as hex (0x1234abcd) without using format_args
 pub fn write_u64_hex(n: u64) {
+    write_hex(&crate::arch::x86::hal::X86, n);
+}
+
+/// `write_u64_hex` against any UART (mockable).
+fn write_hex(out: &impl Uart, n: u64) {
     const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
-    crate::serial::write_str("0x");
+    out.write_str("0x");
     let mut started = false;
     for i in (0..16).rev() {
         let digit = ((n >> (i * 4)) & 0xF) as u8;
         if digit != 0 || started || i == 0 {
-            crate::serial::write_byte(HEX_CHARS[digit as usize]);
+            out.write_byte(HEX_CHARS[digit as usize]);
             started = true;
         }
     }
-    crate::serial::write_str("\n");
+    out.write_str("\n");
 }
 
 /// Write u16 as hex
 pub fn write_u16_hex(n: u16) {
     write_u64_hex(n as u64);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::hal::mock::MockUart;
+
+    #[test]
+    fn test_write_hex() {
+        let out = MockUart::new();
+        write_hex(&out, 0x1a2b);
+        write_hex(&out, 0);
+        let (buf, len) = out.output();
+        assert_eq!(&buf[..len], b"0x1a2b\n0x0\n");
+    }
+}
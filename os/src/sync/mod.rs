@@ -0,0 +1,50 @@
+//! Kernel synchronization primitives
+//!
+//! Spinning primitives only: there is no scheduler to block on yet.
+//!
+//! - `RwLock<T>`: writer-preferring reader/writer spinlock, with
+//!   `*_irqsave` variants for data shared with interrupt handlers
+//! - `SeqLock<T>`: lock-free readers for small, frequently read and rarely
+//!   written `Copy` data (time of day, memory layout table)
+
+mod rwlock;
+mod seqlock;
+
+use crate::kernel::irqsoff;
+
+pub use rwlock::RwLock;
+// SeqLock is the only primitive without a user so far.
+#[allow(unused_imports)]
+pub use seqlock::SeqLock;
+
+/// Restores the interrupt flag saved by an `*_irqsave` lock operation.
+pub(crate) struct IrqRestore {
+    were_enabled: bool,
+    /// Region start for `kernel::irqsoff`, if it is tracing
+    start: Option<u64>,
+    location: &'static core::panic::Location<'static>,
+}
+
+impl IrqRestore {
+    /// Disables interrupts, remembering whether they were enabled.
+    #[inline]
+    #[track_caller]
+    pub(crate) fn save() -> Self {
+        let were_enabled = x86_64::instructions::interrupts::are_enabled();
+        x86_64::instructions::interrupts::disable();
+        let start = if were_enabled { irqsoff::start() } else { None };
+        Self { were_enabled, start, location: core::panic::Location::caller() }
+    }
+}
+
+impl Drop for IrqRestore {
+    #[inline]
+    fn drop(&mut self) {
+        if self.were_enabled {
+            if let Some(start) = self.start {
+                irqsoff::record(irqsoff::RegionKind::IrqsOff, start, self.location);
+            }
+            x86_64::instructions::interrupts::enable();
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Writer-preferring reader/writer spinlock
+//!
+//! # State word
+//! - bit 31: writer holds the lock
+//! - bit 30: a writer is waiting (new readers back off)
+//! - bits 0..30: number of active readers
+//!
+//! Writer preference keeps a steady stream of readers from starving an
+//! update, at the cost of readers spinning while a writer is queued.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::IrqRestore;
+
+const WRITER: u32 = 1 << 31;
+const WRITER_WAITING: u32 = 1 << 30;
+const READER_MASK: u32 = WRITER_WAITING - 1;
+
+/// Reader/writer spinlock protecting a `T`.
+pub struct RwLock<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: access to `data` is serialized by `state`
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates an unlocked `RwLock`.
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Attempts to take a shared lock without spinning.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & (WRITER | WRITER_WAITING) != 0 || state & READER_MASK == READER_MASK {
+            return None;
+        }
+        self.state
+            .compare_exchange(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockReadGuard { lock: self, _irq: None })
+    }
+
+    /// Takes a shared lock, spinning while a writer holds or waits for it.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Attempts to take the exclusive lock without spinning.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & (WRITER | READER_MASK) != 0 {
+            return None;
+        }
+        // Keep WRITER_WAITING if another writer set it; it clears it itself.
+        self.state
+            .compare_exchange(state, state | WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockWriteGuard { lock: self, _irq: None })
+    }
+
+    /// Takes the exclusive lock, blocking new readers while waiting.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Like `read`, with interrupts disabled until the guard is dropped.
+    ///
+    /// Use for data also locked from interrupt handlers, otherwise an IRQ
+    /// arriving while a writer waits on this CPU deadlocks.
+    #[track_caller]
+    pub fn read_irqsave(&self) -> RwLockReadGuard<'_, T> {
+        let irq = IrqRestore::save();
+        let mut guard = self.read();
+        guard._irq = Some(irq);
+        guard
+    }
+
+    /// Like `write`, with interrupts disabled until the guard is dropped.
+    #[track_caller]
+    pub fn write_irqsave(&self) -> RwLockWriteGuard<'_, T> {
+        let irq = IrqRestore::save();
+        let mut guard = self.write();
+        guard._irq = Some(irq);
+        guard
+    }
+
+    /// Returns a mutable reference without locking (exclusive borrow).
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Returns the number of active readers (diagnostics only).
+    pub fn reader_count(&self) -> u32 {
+        self.state.load(Ordering::Relaxed) & READER_MASK
+    }
+}
+
+/// Shared access guard returned by `RwLock::read`.
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    // Dropped after the lock is released (field order)
+    _irq: Option<IrqRestore>,
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: shared lock held, no writer can exist
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Exclusive access guard returned by `RwLock::write`.
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    _irq: Option<IrqRestore>,
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: exclusive lock held
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: exclusive lock held
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // Clears WRITER_WAITING as well; other spinning writers set it again.
+        self.lock.state.fetch_and(!(WRITER | WRITER_WAITING), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readers_share() {
+        let lock = RwLock::new(5);
+        let a = lock.read();
+        let b = lock.read();
+        assert_eq!(*a + *b, 10);
+        assert_eq!(lock.reader_count(), 2);
+        assert!(lock.try_write().is_none());
+    }
+
+    #[test]
+    fn test_writer_excludes() {
+        let lock = RwLock::new(0);
+        {
+            let mut w = lock.write();
+            *w = 7;
+            assert!(lock.try_read().is_none());
+            assert!(lock.try_write().is_none());
+        }
+        assert_eq!(*lock.read(), 7);
+    }
+
+    #[test]
+    fn test_concurrent_readers_and_writer() {
+        const WRITES: u64 = 20_000;
+        // Writers keep both halves equal; a reader seeing them differ
+        // overlapped a writer.
+        let lock = RwLock::new((0u64, 0u64));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| loop {
+                    let pair = *lock.read();
+                    assert_eq!(pair.0, pair.1);
+                    if pair.0 == WRITES {
+                        break;
+                    }
+                });
+            }
+            s.spawn(|| {
+                for _ in 0..WRITES {
+                    let mut w = lock.write();
+                    w.0 += 1;
+                    std::hint::spin_loop();
+                    w.1 += 1;
+                }
+            });
+        });
+        assert_eq!(*lock.read(), (WRITES, WRITES));
+        assert_eq!(lock.reader_count(), 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Validated access to user memory
+//!
+//! Every pointer or length that comes from user space goes through this
+//! module before the kernel touches it. Checks, in order:
+//! 1. `addr + len` does not overflow and stays below `USER_SPACE_END`
+//! 2. every page in the range is mapped PRESENT | USER_ACCESSIBLE, plus
+//!    WRITABLE for writes, in the target address space
+//! 3. the copy itself goes through the fault-tolerant routines in
+//!    `arch::x86::usercopy`, so a mapping that disappears between the check
+//!    and the copy still returns `Fault` instead of halting
+//!
+//! Permissions are the effective flags of the whole walk
+//! (`AddressSpace::translate`), not just the leaf entry: a PDPTE or PDE
+//! with USER or WRITABLE cleared denies access to everything below it.
+//!
+//! `read_user`/`write_user` move whole values and are limited to `Pod`
+//! types, so user bytes never become an invalid value and no padding is
+//! copied out of the kernel.
+//!
+//! Stage 2C+: the page-table check becomes a VMA lookup once address
+//! spaces track regions.
+
+use crate::arch::x86::usercopy;
+use crate::paging::{AddressSpace, USER_SPACE_END};
+use x86_64::{structures::paging::PageTableFlags as Flags, VirtAddr};
+
+/// errno value returned to user space for any bad user pointer
+pub const EFAULT: i64 = 14;
+
+/// Access a syscall needs on a user range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Invalid user pointer (reported to user space as EFAULT)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UaccessError {
+    /// Range overflows or reaches into kernel space
+    BadRange { addr: u64, len: u64 },
+    /// Page not mapped, or mapped without the required permission
+    NotMapped { addr: u64 },
+    /// Hardware fault during the copy itself
+    Fault { addr: u64 },
+    /// Address space is not loaded in CR3, so its pointers cannot be used
+    NotActive,
+}
+
+impl UaccessError {
+    /// Returns the negated errno for the syscall return path.
+    pub fn errno(&self) -> i64 {
+        -EFAULT
+    }
+}
+
+/// Range-only check: true if `[addr, addr + len)` lies in user space.
+///
+/// Zero-length ranges are accepted at any user address.
+#[inline]
+pub fn access_ok(addr: u64, len: u64) -> bool {
+    match addr.checked_add(len) {
+        Some(end) => addr < USER_SPACE_END && end <= USER_SPACE_END,
+        None => false,
+    }
+}
+
+/// Verifies that every page of `[addr, addr + len)` allows `access`.
+///
+/// # Errors
+/// - `BadRange` if the range fails `access_ok`
+/// - `NotMapped` with the first offending address otherwise
+pub fn check_range(
+    space: &AddressSpace,
+    addr: u64,
+    len: u64,
+    access: Access,
+) -> Result<(), UaccessError> {
+    if !access_ok(addr, len) {
+        return Err(UaccessError::BadRange { addr, len });
+    }
+    if len == 0 {
+        return Ok(());
+    }
+
+    let mut required = Flags::PRESENT | Flags::USER_ACCESSIBLE;
+    if access == Access::Write {
+        required |= Flags::WRITABLE;
+    }
+
+    let mut page = addr & !0xFFF;
+    let end = addr + len;
+    while page < end {
+        match space.translate(VirtAddr::new(page)) {
+            Some((_, flags)) if flags.contains(required) => {}
+            _ => return Err(UaccessError::NotMapped { addr: page.max(addr) }),
+        }
+        page += 0x1000;
+    }
+    Ok(())
+}
+
+/// Copies `dst.len()` bytes from user address `src` in the active `space`.
+pub fn copy_from_user(space: &mut AddressSpace, dst: &mut [u8], src: u64) -> Result<(), UaccessError> {
+    if !space.is_active() {
+        return Err(UaccessError::NotActive);
+    }
+    check_range(space, src, dst.len() as u64, Access::Read)?;
+
+    // SAFETY: range validated as mapped user memory of the active space
+    unsafe { usercopy::raw_copy_from_user(dst, src as *const u8) }.map_err(|f| UaccessError::Fault {
+        addr: src + (dst.len() - f.remaining) as u64,
+    })
+}
+
+/// Copies `src` to user address `dst` in the active `space`.
+pub fn copy_to_user(space: &mut AddressSpace, dst: u64, src: &[u8]) -> Result<(), UaccessError> {
+    if !space.is_active() {
+        return Err(UaccessError::NotActive);
+    }
+    check_range(space, dst, src.len() as u64, Access::Write)?;
+
+    // SAFETY: range validated as writable user memory of the active space
+    unsafe { usercopy::raw_copy_to_user(dst as *mut u8, src) }.map_err(|f| UaccessError::Fault {
+        addr: dst + (src.len() - f.remaining) as u64,
+    })
+}
+
+/// Plain data that can be copied to and from user memory as raw bytes.
+///
+/// # Safety
+/// Implementors must have no padding bytes and be valid for every bit
+/// pattern: no `bool`, `char`, enums, references or `NonNull`.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => { $(unsafe impl Pod for $t {})* };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Reads a plain-data value from user memory (no alignment requirement).
+pub fn read_user<T: Pod>(space: &mut AddressSpace, addr: u64) -> Result<T, UaccessError> {
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    // SAFETY: the byte view covers exactly the storage of `value`
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, core::mem::size_of::<T>())
+    };
+    copy_from_user(space, bytes, addr)?;
+    // SAFETY: fully initialized by the copy; Pod accepts any bit pattern
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Writes a plain-data value to user memory (no alignment requirement).
+pub fn write_user<T: Pod>(space: &mut AddressSpace, addr: u64, value: &T) -> Result<(), UaccessError> {
+    // SAFETY: Pod has no padding, so every byte of `value` is initialized
+    let bytes = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    copy_to_user(space, addr, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_ok() {
+        assert!(access_ok(0x1000, 0x1000));
+        assert!(access_ok(USER_SPACE_END - 0x1000, 0x1000));
+        assert!(access_ok(0x1000, 0));
+
+        assert!(!access_ok(USER_SPACE_END - 0x1000, 0x1001));
+        assert!(!access_ok(USER_SPACE_END, 0));
+        assert!(!access_ok(u64::MAX, 2));
+    }
+}
@@ -42,10 +42,17 @@ OVMF_VARS=/tmp/OVMF_VARS.fd
 # --- Вибір режиму: BIOS або UEFI ---
 MODE="${1:-uefi}"  # default uefi
 
+# --- Профіль ядра: debug (os-*.img) або release (os-*-release.img) ---
+case "${OS_PROFILE:-debug}" in
+    debug) SUFFIX="" ;;
+    release) SUFFIX="-release" ;;
+    *) echo "Unknown OS_PROFILE: $OS_PROFILE. Use 'debug' or 'release'."; exit 1 ;;
+esac
+
 if [[ "$MODE" == "bios" ]]; then
-    IMG=os-bios.img
+    IMG=os-bios$SUFFIX.img
 elif [[ "$MODE" == "uefi" ]]; then
-    IMG=os-uefi.img
+    IMG=os-uefi$SUFFIX.img
 
     # завжди створюємо чисту writable копію для розробки
     cp "$OVMF_VARS_TEMPLATE" "$OVMF_VARS"
@@ -55,19 +62,45 @@ else
 fi
 
 if [ ! -f "$IMG" ]; then
-    echo "Disk image '$IMG' not found. Run: cargo run -p boot"
+    echo "Disk image '$IMG' not found. Run: make image (or make image-release)"
     exit 1
 fi
 
 # --- Загальні параметри QEMU ---
+# OS_MEM=1G          -> розмір пам'яті (за замовчуванням 512M)
+# OS_SMP=2           -> кількість CPU (за замовчуванням 1)
+# OS_NIC=e1000       -> модель мережевої карти (user networking); без неї - типова для q35
+# OS_DISKS=a.img,b.img -> додаткові raw-диски
 QEMU_COMMON=(
-  -m 512M
+  -m "${OS_MEM:-512M}"
+  -smp "${OS_SMP:-1}"
   -cpu qemu64
   -machine q35
   -serial stdio
   -display none
   -drive file="$IMG",format=raw
 )
+if [[ -n "${OS_NIC:-}" ]]; then
+    QEMU_COMMON+=(-nic "user,model=$OS_NIC")
+fi
+if [[ -n "${OS_DISKS:-}" ]]; then
+    IFS=',' read -ra DISKS <<< "$OS_DISKS"
+    for disk in "${DISKS[@]}"; do
+        QEMU_COMMON+=(-drive "file=$disk,format=raw,media=disk")
+    done
+fi
+
+# --- Параметри для гостя через fw_cfg (необов'язково) ---
+# OS_CMDLINE="..." ./run-qemu.sh   -> opt/os/cmdline (напр. "pic=off": LAPIC замість 8259)
+# OS_TEST=scenario ./run-qemu.sh   -> opt/os/test
+# QEMU ділить значення опцій по ',', тому коми екрануються як ',,'
+# (напр. "blacklist=ps2_keyboard,ata").
+if [[ -n "${OS_CMDLINE:-}" ]]; then
+    QEMU_COMMON+=(-fw_cfg "name=opt/os/cmdline,string=${OS_CMDLINE//,/,,}")
+fi
+if [[ -n "${OS_TEST:-}" ]]; then
+    QEMU_COMMON+=(-fw_cfg "name=opt/os/test,string=${OS_TEST//,/,,}")
+fi
 
 if [[ "$MODE" == "uefi" ]]; then
     exec stdbuf -o0 -e0 qemu-system-x86_64 \
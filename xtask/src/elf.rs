@@ -0,0 +1,123 @@
+//! Minimal ELF64 little-endian reader: section headers and the symbol table.
+
+use std::fmt;
+
+const SHT_SYMTAB: u32 = 2;
+const SHF_ALLOC: u64 = 0x2;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+#[derive(Debug)]
+pub struct ElfError(&'static str);
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad ELF: {}", self.0)
+    }
+}
+
+pub struct Section {
+    pub name: String,
+    pub size: u64,
+    /// Occupies memory at run time
+    pub alloc: bool,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub size: u64,
+}
+
+pub struct Elf {
+    pub sections: Vec<Section>,
+    /// Function and object symbols with a size
+    pub symbols: Vec<Symbol>,
+}
+
+fn read<const N: usize>(data: &[u8], off: usize) -> Result<[u8; N], ElfError> {
+    data.get(off..off + N)
+        .and_then(|s| s.try_into().ok())
+        .ok_or(ElfError("truncated"))
+}
+
+fn u16_at(data: &[u8], off: usize) -> Result<u16, ElfError> {
+    read(data, off).map(u16::from_le_bytes)
+}
+
+fn u32_at(data: &[u8], off: usize) -> Result<u32, ElfError> {
+    read(data, off).map(u32::from_le_bytes)
+}
+
+fn u64_at(data: &[u8], off: usize) -> Result<u64, ElfError> {
+    read(data, off).map(u64::from_le_bytes)
+}
+
+fn c_str(data: &[u8], off: usize) -> String {
+    let tail = data.get(off..).unwrap_or_default();
+    let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    String::from_utf8_lossy(&tail[..end]).into_owned()
+}
+
+struct RawSection {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    entsize: u64,
+}
+
+impl Elf {
+    pub fn parse(data: &[u8]) -> Result<Self, ElfError> {
+        if data.get(..4) != Some(b"\x7fELF") || data.get(4) != Some(&2) || data.get(5) != Some(&1) {
+            return Err(ElfError("not a little-endian ELF64 file"));
+        }
+        let shoff = u64_at(data, 0x28)? as usize;
+        let shentsize = u16_at(data, 0x3A)? as usize;
+        let shnum = u16_at(data, 0x3C)? as usize;
+        let shstrndx = u16_at(data, 0x3E)? as usize;
+
+        let raw = (0..shnum)
+            .map(|i| {
+                let base = shoff + i * shentsize;
+                Ok(RawSection {
+                    name: u32_at(data, base)?,
+                    kind: u32_at(data, base + 4)?,
+                    flags: u64_at(data, base + 8)?,
+                    offset: u64_at(data, base + 0x18)?,
+                    size: u64_at(data, base + 0x20)?,
+                    link: u32_at(data, base + 0x28)?,
+                    entsize: u64_at(data, base + 0x38)?,
+                })
+            })
+            .collect::<Result<Vec<_>, ElfError>>()?;
+
+        let shstr = raw.get(shstrndx).ok_or(ElfError("no section name table"))?;
+        let sections = raw
+            .iter()
+            .map(|s| Section {
+                name: c_str(data, shstr.offset as usize + s.name as usize),
+                size: s.size,
+                alloc: s.flags & SHF_ALLOC != 0,
+            })
+            .collect();
+
+        let mut symbols = Vec::new();
+        if let Some(symtab) = raw.iter().find(|s| s.kind == SHT_SYMTAB) {
+            let strtab = raw.get(symtab.link as usize).ok_or(ElfError("no symbol string table"))?;
+            let entsize = symtab.entsize.max(24) as usize;
+            for i in 0..(symtab.size as usize / entsize) {
+                let base = symtab.offset as usize + i * entsize;
+                let info = read::<1>(data, base + 4)?[0];
+                let size = u64_at(data, base + 16)?;
+                if size == 0 || !matches!(info & 0xF, STT_FUNC | STT_OBJECT) {
+                    continue;
+                }
+                let name = c_str(data, strtab.offset as usize + u32_at(data, base)? as usize);
+                symbols.push(Symbol { name, size });
+            }
+        }
+        Ok(Self { sections, symbols })
+    }
+}
@@ -0,0 +1,476 @@
+//! ext2: block ownership is rebuilt from the group metadata and from every
+//! inode reachable from the root directory (plus the reserved inodes), then
+//! compared with the on-disk bitmaps, free counts and link counts.
+
+use super::{le16, le32, Disk, Report};
+
+const SUPERBLOCK: u64 = 1024;
+const MAGIC: u16 = 0xEF53;
+const ROOT_INO: u32 = 2;
+
+/// Largest `s_log_block_size`: 64 KiB blocks
+const MAX_LOG_BLOCK_SIZE: u32 = 6;
+
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+
+const S_IFMT: u16 = 0xF000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFREG: u16 = 0x8000;
+const S_IFLNK: u16 = 0xA000;
+
+const EXTENTS_FL: u32 = 0x0008_0000;
+
+/// Who a block was first claimed by
+#[derive(Clone, Copy, PartialEq)]
+enum Owner {
+    Metadata,
+    Inode(u32),
+    /// Extended attribute blocks are shared by design
+    Xattr,
+}
+
+struct Fs<'a> {
+    disk: Disk<'a>,
+    block_size: u64,
+    blocks_count: u64,
+    first_data_block: u64,
+    blocks_per_group: u64,
+    inodes_count: u32,
+    inodes_per_group: u32,
+    inode_size: u64,
+    first_ino: u32,
+    groups: Vec<Group>,
+}
+
+struct Group {
+    block_bitmap: u64,
+    inode_bitmap: u64,
+    inode_table: u64,
+    free_blocks: u16,
+    free_inodes: u16,
+}
+
+struct Inode {
+    mode: u16,
+    links: u16,
+    /// In 512-byte units, metadata blocks included
+    sectors: u32,
+    flags: u32,
+    size: u64,
+    file_acl: u64,
+    block: [u32; 15],
+}
+
+impl Inode {
+    fn kind(&self) -> u16 {
+        self.mode & S_IFMT
+    }
+
+    /// Devices, FIFOs, sockets and fast symlinks use `block` for other data
+    fn has_blocks(&self, fs: &Fs) -> bool {
+        let acl_sectors = if self.file_acl != 0 { fs.block_size / 512 } else { 0 };
+        match self.kind() {
+            S_IFDIR | S_IFREG => true,
+            S_IFLNK => self.sectors as u64 > acl_sectors,
+            0 => self.sectors != 0,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Fs<'a> {
+    fn block(&self, n: u64) -> Result<&'a [u8], String> {
+        self.disk.slice(n * self.block_size, self.block_size)
+    }
+
+    fn is_data_block(&self, n: u64) -> bool {
+        (self.first_data_block..self.blocks_count).contains(&n)
+    }
+
+    fn inode(&self, ino: u32) -> Result<Inode, String> {
+        let group = ino
+            .checked_sub(1)
+            .and_then(|i| self.groups.get((i / self.inodes_per_group) as usize))
+            .ok_or_else(|| format!("inode {ino} outside every group"))?;
+        let index = ((ino - 1) % self.inodes_per_group) as u64;
+        let raw = self.disk.slice(group.inode_table * self.block_size + index * self.inode_size, 128)?;
+        let mut block = [0u32; 15];
+        for (i, b) in block.iter_mut().enumerate() {
+            *b = le32(raw, 40 + i * 4);
+        }
+        let mode = le16(raw, 0);
+        let size_high = if mode & S_IFMT == S_IFREG { le32(raw, 108) as u64 } else { 0 };
+        Ok(Inode {
+            mode,
+            links: le16(raw, 26),
+            sectors: le32(raw, 28),
+            flags: le32(raw, 32),
+            size: size_high << 32 | le32(raw, 4) as u64,
+            file_acl: le32(raw, 104) as u64,
+            block,
+        })
+    }
+
+    /// Groups 0, 1 and powers of 3, 5 and 7 carry superblock backups with
+    /// `sparse_super`; every group does without it.
+    fn has_superblock(&self, group: u64, sparse: bool) -> bool {
+        fn is_power_of(mut n: u64, base: u64) -> bool {
+            while n > 1 && n.is_multiple_of(base) {
+                n /= base;
+            }
+            n == 1
+        }
+        !sparse || group <= 1 || [3, 5, 7].iter().any(|&b| is_power_of(group, b))
+    }
+}
+
+fn bit(bitmap: &[u8], n: u64) -> bool {
+    bitmap.get((n / 8) as usize).is_some_and(|b| b & (1 << (n % 8)) != 0)
+}
+
+pub fn detect(disk: Disk) -> bool {
+    disk.u16(SUPERBLOCK + 56).is_ok_and(|m| m == MAGIC)
+}
+
+/// Marks block `n` as used by `owner`, reporting collisions and bad numbers.
+fn claim(fs: &Fs, used: &mut [Option<Owner>], n: u64, owner: Owner, report: &mut Report) -> bool {
+    let who = |o: Owner| match o {
+        Owner::Metadata => "filesystem metadata".to_string(),
+        Owner::Inode(ino) => format!("inode {ino}"),
+        Owner::Xattr => "an xattr block".to_string(),
+    };
+    if !fs.is_data_block(n) {
+        report.problem(format!("{}: illegal block number {n}", who(owner)));
+        return false;
+    }
+    match used[n as usize] {
+        None => used[n as usize] = Some(owner),
+        Some(Owner::Xattr) if owner == Owner::Xattr => {}
+        Some(prev) => {
+            report.problem(format!("block {n} claimed by both {} and {}", who(prev), who(owner)));
+            return false;
+        }
+    }
+    true
+}
+
+/// Claims every block of `inode` and returns its data blocks in file order.
+fn walk_inode(fs: &Fs, used: &mut [Option<Owner>], ino: u32, inode: &Inode, report: &mut Report) -> Result<Vec<u64>, String> {
+    let mut data = Vec::new();
+    let mut claimed = 0u64;
+    if inode.file_acl != 0 && claim(fs, used, inode.file_acl, Owner::Xattr, report) {
+        claimed += 1;
+    }
+    if !inode.has_blocks(fs) {
+        return Ok(data);
+    }
+    if inode.flags & EXTENTS_FL != 0 {
+        report.problem(format!("inode {ino}: uses extents, not ext2"));
+        return Ok(data);
+    }
+
+    // (block, indirection depth)
+    let mut pending: Vec<(u64, u32)> = inode.block[..12].iter().map(|&b| (b as u64, 0)).collect();
+    pending.extend([(inode.block[12] as u64, 1), (inode.block[13] as u64, 2), (inode.block[14] as u64, 3)]);
+    pending.reverse();
+    while let Some((n, depth)) = pending.pop() {
+        if n == 0 || !claim(fs, used, n, Owner::Inode(ino), report) {
+            continue;
+        }
+        claimed += 1;
+        if depth == 0 {
+            data.push(n);
+            continue;
+        }
+        let table = fs.block(n)?;
+        // Pushed in reverse so that pop() keeps file order
+        for entry in table.as_chunks::<4>().0.iter().rev() {
+            pending.push((u32::from_le_bytes(*entry) as u64, depth - 1));
+        }
+    }
+
+    let expected = claimed * (fs.block_size / 512);
+    if inode.sectors as u64 != expected {
+        report.problem(format!("inode {ino}: i_blocks is {}, counted {expected}", inode.sectors));
+    }
+    if inode.kind() == S_IFREG && inode.size.div_ceil(fs.block_size) < data.len() as u64 {
+        report.problem(format!("inode {ino}: {} data blocks past its size of {}", data.len(), inode.size));
+    }
+    Ok(data)
+}
+
+pub fn check(disk: Disk, report: &mut Report) -> Result<(), String> {
+    let sb = |off: u64| disk.u32(SUPERBLOCK + off);
+    let rev_level = sb(76)?;
+    let incompat = sb(96)?;
+    if incompat & !INCOMPAT_FILETYPE != 0 {
+        return Err(format!("ext2: unsupported incompatible features {incompat:#x}"));
+    }
+    let sparse = sb(100)? & RO_COMPAT_SPARSE_SUPER != 0;
+
+    let log_block_size = sb(24)?;
+    if log_block_size > MAX_LOG_BLOCK_SIZE {
+        report.problem(format!("superblock: block size 1024 << {log_block_size} is not valid"));
+        return Ok(());
+    }
+    let mut fs = Fs {
+        disk,
+        block_size: 1024 << log_block_size,
+        blocks_count: sb(4)? as u64,
+        first_data_block: sb(20)? as u64,
+        blocks_per_group: sb(32)? as u64,
+        inodes_count: sb(0)?,
+        inodes_per_group: sb(40)?,
+        inode_size: if rev_level == 0 { 128 } else { disk.u16(SUPERBLOCK + 88)? as u64 },
+        first_ino: if rev_level == 0 { 11 } else { sb(84)? },
+        groups: Vec::new(),
+    };
+    let geometry = geometry_problems(&fs, disk.0.len() as u64);
+    if !geometry.is_empty() {
+        for problem in geometry {
+            report.problem(format!("superblock: {problem}"));
+        }
+        return Ok(());
+    }
+    let group_count = (fs.blocks_count - fs.first_data_block).div_ceil(fs.blocks_per_group);
+    let gdt = disk.slice((fs.first_data_block + 1) * fs.block_size, group_count * 32)?;
+    fs.groups = gdt
+        .as_chunks::<32>()
+        .0
+        .iter()
+        .map(|d| Group {
+            block_bitmap: le32(d, 0) as u64,
+            inode_bitmap: le32(d, 4) as u64,
+            inode_table: le32(d, 8) as u64,
+            free_blocks: le16(d, 12),
+            free_inodes: le16(d, 14),
+        })
+        .collect();
+
+    let mut used: Vec<Option<Owner>> = vec![None; fs.blocks_count as usize];
+
+    // Superblock and descriptor copies, bitmaps and inode tables. Reserved
+    // GDT blocks belong to the resize inode and are claimed through it.
+    let gdt_blocks = (group_count * 32).div_ceil(fs.block_size);
+    let table_blocks = (fs.inodes_per_group as u64 * fs.inode_size).div_ceil(fs.block_size);
+    for (g, group) in fs.groups.iter().enumerate() {
+        let start = fs.first_data_block + g as u64 * fs.blocks_per_group;
+        let mut meta = vec![group.block_bitmap, group.inode_bitmap];
+        meta.extend(group.inode_table..group.inode_table + table_blocks);
+        if fs.has_superblock(g as u64, sparse) {
+            meta.extend(start..start + 1 + gdt_blocks);
+        }
+        for n in meta {
+            claim(&fs, &mut used, n, Owner::Metadata, report);
+        }
+    }
+
+    // References to each inode from directory entries
+    let mut refs = vec![0u32; fs.inodes_count as usize + 1];
+    let mut walked = vec![false; fs.inodes_count as usize + 1];
+
+    for ino in 1..fs.first_ino.min(fs.inodes_count + 1) {
+        if ino == ROOT_INO {
+            continue;
+        }
+        let inode = fs.inode(ino)?;
+        walk_inode(&fs, &mut used, ino, &inode, report)?;
+        walked[ino as usize] = true;
+    }
+
+    let mut dirs = vec![ROOT_INO];
+    walked[ROOT_INO as usize] = true;
+    while let Some(dir) = dirs.pop() {
+        let inode = fs.inode(dir)?;
+        if inode.kind() != S_IFDIR {
+            report.problem(format!("inode {dir}: expected a directory"));
+            continue;
+        }
+        for n in walk_inode(&fs, &mut used, dir, &inode, report)? {
+            let block = fs.block(n)?;
+            let mut off = 0usize;
+            while off + 8 <= block.len() {
+                let ino = le32(block, off);
+                let rec_len = le16(block, off + 4) as usize;
+                let name_len = block[off + 6] as usize;
+                if rec_len < 8 || off + rec_len > block.len() || 8 + name_len > rec_len {
+                    report.problem(format!("directory inode {dir}: corrupt entry in block {n} at offset {off}"));
+                    break;
+                }
+                let name = String::from_utf8_lossy(&block[off + 8..off + 8 + name_len]).into_owned();
+                off += rec_len;
+                if ino == 0 {
+                    continue;
+                }
+                if ino > fs.inodes_count {
+                    report.problem(format!("directory inode {dir}: entry '{name}' points to inode {ino} out of range"));
+                    continue;
+                }
+                refs[ino as usize] += 1;
+                if name == "." || name == ".." || walked[ino as usize] {
+                    continue;
+                }
+                walked[ino as usize] = true;
+                let child = fs.inode(ino)?;
+                if child.mode == 0 || child.links == 0 {
+                    report.problem(format!("directory inode {dir}: entry '{name}' points to unused inode {ino}"));
+                } else if child.kind() == S_IFDIR {
+                    dirs.push(ino);
+                } else {
+                    walk_inode(&fs, &mut used, ino, &child, report)?;
+                }
+            }
+        }
+    }
+
+    // Link counts of everything reachable
+    for ino in (ROOT_INO..=fs.inodes_count).filter(|&i| walked[i as usize] && (i == ROOT_INO || i >= fs.first_ino)) {
+        let inode = fs.inode(ino)?;
+        if inode.mode != 0 && inode.links as u32 != refs[ino as usize] {
+            report.problem(format!("inode {ino}: link count {}, {} directory entries", inode.links, refs[ino as usize]));
+        }
+    }
+
+    // Bitmaps against what was rebuilt
+    let (mut unclaimed, mut unmarked) = (Vec::new(), Vec::new());
+    let (mut orphans, mut free_but_linked) = (Vec::new(), Vec::new());
+    let (mut free_blocks, mut free_inodes) = (0u64, 0u64);
+    for (g, group) in fs.groups.iter().enumerate() {
+        let bitmap = fs.block(group.block_bitmap)?;
+        let start = fs.first_data_block + g as u64 * fs.blocks_per_group;
+        let end = (start + fs.blocks_per_group).min(fs.blocks_count);
+        let mut group_free = 0u64;
+        for n in start..end {
+            match (bit(bitmap, n - start), used[n as usize].is_some()) {
+                (true, false) => unclaimed.push(n),
+                (false, true) => unmarked.push(n),
+                (false, false) => group_free += 1,
+                (true, true) => {}
+            }
+        }
+        if group_free != group.free_blocks as u64 {
+            report.problem(format!("group {g}: {} free blocks recorded, bitmap has {group_free}", group.free_blocks));
+        }
+        free_blocks += group_free;
+
+        let bitmap = fs.block(group.inode_bitmap)?;
+        let first = g as u64 * fs.inodes_per_group as u64 + 1;
+        let last = (first + fs.inodes_per_group as u64).min(fs.inodes_count as u64 + 1);
+        let mut group_free = 0u64;
+        for ino in (first..last).map(|i| i as u32) {
+            let marked = bit(bitmap, ino as u64 - first);
+            let reachable = walked[ino as usize] && (ino < fs.first_ino || refs[ino as usize] > 0);
+            if !marked {
+                group_free += 1;
+            }
+            if marked && !reachable && ino >= fs.first_ino {
+                orphans.push(ino as u64);
+            } else if !marked && reachable {
+                free_but_linked.push(ino as u64);
+            }
+        }
+        if group_free != group.free_inodes as u64 {
+            report.problem(format!("group {g}: {} free inodes recorded, bitmap has {group_free}", group.free_inodes));
+        }
+        free_inodes += group_free;
+    }
+    report.numbers("blocks in use but free in the bitmap", &unmarked);
+    report.numbers("blocks marked in the bitmap but unreferenced", &unclaimed);
+    report.numbers("inodes in use but free in the bitmap", &free_but_linked);
+    report.numbers("orphan inodes (allocated, not linked)", &orphans);
+
+    // The superblock counts are only brought up to date at unmount, so a
+    // crashed but otherwise consistent volume is expected to differ here
+    let (sb_free_blocks, sb_free_inodes) = (sb(12)? as u64, sb(16)? as u64);
+    if sb_free_blocks != free_blocks {
+        report.warning(format!("superblock: {sb_free_blocks} free blocks recorded, bitmaps have {free_blocks}"));
+    }
+    if sb_free_inodes != free_inodes {
+        report.warning(format!("superblock: {sb_free_inodes} free inodes recorded, bitmaps have {free_inodes}"));
+    }
+    Ok(())
+}
+
+/// Superblock values the rest of the check relies on; any of these being
+/// off means the counts and offsets derived from them are meaningless.
+fn geometry_problems(fs: &Fs, image_len: u64) -> Vec<String> {
+    let mut problems = Vec::new();
+    if fs.blocks_per_group == 0 || fs.inodes_per_group == 0 {
+        problems.push("zero blocks or inodes per group".to_string());
+        return problems;
+    }
+    if fs.inode_size < 128 || !fs.inode_size.is_power_of_two() || fs.inode_size > fs.block_size {
+        problems.push(format!("bad inode size {}", fs.inode_size));
+    }
+    if fs.first_data_block >= fs.blocks_count {
+        problems.push(format!("first data block {} not below block count {}", fs.first_data_block, fs.blocks_count));
+        return problems;
+    }
+    if fs.blocks_count.checked_mul(fs.block_size).is_none_or(|len| len > image_len) {
+        problems.push(format!("{} blocks of {} bytes do not fit the {image_len}-byte image", fs.blocks_count, fs.block_size));
+    }
+    // One bitmap block per group
+    let bitmap_bits = 8 * fs.block_size;
+    if fs.blocks_per_group > bitmap_bits || fs.inodes_per_group as u64 > bitmap_bits {
+        problems.push(format!(
+            "{} blocks / {} inodes per group exceed a {bitmap_bits}-bit bitmap",
+            fs.blocks_per_group, fs.inodes_per_group
+        ));
+    }
+    let group_count = (fs.blocks_count - fs.first_data_block).div_ceil(fs.blocks_per_group);
+    if fs.inodes_count as u64 > group_count * fs.inodes_per_group as u64 {
+        problems.push(format!(
+            "{} inodes do not fit {group_count} groups of {}",
+            fs.inodes_count, fs.inodes_per_group
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Superblock only: 1 KiB blocks, 64 blocks in one group of 16 inodes
+    fn image() -> Vec<u8> {
+        let mut img = vec![0u8; 64 * 1024];
+        let mut set = |off: usize, value: u32| {
+            img[1024 + off..1024 + off + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        set(0, 16);
+        set(4, 64);
+        set(20, 1);
+        set(32, 8192);
+        set(40, 16);
+        img[1024 + 56..1024 + 58].copy_from_slice(&MAGIC.to_le_bytes());
+        img
+    }
+
+    fn problems(img: &[u8]) -> Vec<String> {
+        let disk = Disk(img);
+        assert!(detect(disk));
+        let mut report = Report::default();
+        check(disk, &mut report).unwrap();
+        report.problems
+    }
+
+    #[test]
+    fn test_bad_geometry_is_reported() {
+        let mut img = image();
+        img[1024 + 24] = 60;
+        assert_eq!(problems(&img), ["superblock: block size 1024 << 60 is not valid"]);
+
+        let mut img = image();
+        img[1024 + 20] = 100;
+        assert_eq!(problems(&img), ["superblock: first data block 100 not below block count 64"]);
+
+        let mut img = image();
+        img[1024 + 32..1024 + 36].copy_from_slice(&9000u32.to_le_bytes());
+        img[1024..1024 + 4].copy_from_slice(&17u32.to_le_bytes());
+        let found = problems(&img);
+        assert_eq!(found.len(), 2, "{found:?}");
+        assert!(found[0].contains("exceed a 8192-bit bitmap"));
+        assert!(found[1].contains("17 inodes do not fit 1 groups of 16"));
+    }
+}
@@ -0,0 +1,257 @@
+//! FAT32: every cluster chain reachable from the root directory is walked
+//! and claimed; chains that collide, end early or run off the FAT are
+//! reported, as are allocated clusters nobody reaches.
+
+use super::{le16, le32, Disk, Report};
+
+const FREE: u32 = 0;
+const BAD: u32 = 0x0FFF_FFF7;
+const END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+const DIRENT_SIZE: usize = 32;
+
+struct Volume<'a> {
+    disk: Disk<'a>,
+    cluster_bytes: u64,
+    /// Byte offset of cluster 2
+    data_start: u64,
+    /// Entries of the first FAT
+    fat: Vec<u32>,
+    /// Highest valid cluster number + 1
+    cluster_end: u32,
+}
+
+impl Volume<'_> {
+    fn entry(&self, cluster: u32) -> u32 {
+        self.fat[cluster as usize] & 0x0FFF_FFFF
+    }
+
+    fn is_data_cluster(&self, cluster: u32) -> bool {
+        (2..self.cluster_end).contains(&cluster)
+    }
+
+    fn read_cluster(&self, cluster: u32) -> Result<&[u8], String> {
+        let off = self.data_start + (cluster as u64 - 2) * self.cluster_bytes;
+        self.disk.slice(off, self.cluster_bytes)
+    }
+}
+
+pub fn detect(disk: Disk) -> bool {
+    disk.slice(82, 8).is_ok_and(|t| t == b"FAT32   ") && disk.u16(510).is_ok_and(|s| s == 0xAA55)
+}
+
+/// Walks the chain starting at `start` on behalf of file `owner`.
+///
+/// Stops at the first cluster that is invalid or already claimed; whatever
+/// was claimed before that is returned.
+fn walk_chain(
+    vol: &Volume,
+    owners: &mut [Option<usize>],
+    paths: &[String],
+    owner: usize,
+    start: u32,
+    report: &mut Report,
+) -> Vec<u32> {
+    let path = &paths[owner];
+    let mut chain = Vec::new();
+    let mut cluster = start;
+    loop {
+        if !vol.is_data_cluster(cluster) {
+            report.problem(format!("{path}: chain points to invalid cluster {cluster}"));
+            break;
+        }
+        match owners[cluster as usize] {
+            Some(o) if o == owner => {
+                report.problem(format!("{path}: chain loops back to cluster {cluster}"));
+                break;
+            }
+            Some(o) => {
+                report.problem(format!("{path}: cross-linked with {} at cluster {cluster}", paths[o]));
+                break;
+            }
+            None => owners[cluster as usize] = Some(owner),
+        }
+        chain.push(cluster);
+        match vol.entry(cluster) {
+            FREE => {
+                report.problem(format!("{path}: chain runs into free cluster {cluster}"));
+                break;
+            }
+            BAD => {
+                report.problem(format!("{path}: chain contains bad cluster {cluster}"));
+                break;
+            }
+            next if next >= END_OF_CHAIN => break,
+            next => cluster = next,
+        }
+    }
+    chain
+}
+
+/// `NAME.EXT` from the 8.3 name field
+fn short_name(raw: &[u8]) -> String {
+    let base = String::from_utf8_lossy(&raw[..8]).trim_end().to_string();
+    let ext = String::from_utf8_lossy(&raw[8..11]).trim_end().to_string();
+    if ext.is_empty() { base } else { format!("{base}.{ext}") }
+}
+
+pub fn check(disk: Disk, report: &mut Report) -> Result<(), String> {
+    let bytes_per_sector = disk.u16(11)? as u64;
+    let sectors_per_cluster = disk.u8(13)? as u64;
+    let reserved = disk.u16(14)? as u64;
+    let fat_count = disk.u8(16)? as u64;
+    let total_sectors = match disk.u16(19)? {
+        0 => disk.u32(32)? as u64,
+        n => n as u64,
+    };
+    let fat_sectors = disk.u32(36)? as u64;
+    let root_cluster = disk.u32(44)?;
+
+    if bytes_per_sector == 0 || sectors_per_cluster == 0 || fat_count == 0 {
+        return Err("FAT32: zero sector size, cluster size or FAT count in BPB".into());
+    }
+    let data_sector = reserved + fat_count * fat_sectors;
+    let clusters = total_sectors.saturating_sub(data_sector) / sectors_per_cluster;
+    let fat_entries = fat_sectors * bytes_per_sector / 4;
+    if fat_entries < clusters + 2 {
+        return Err(format!("FAT32: FAT holds {fat_entries} entries for {clusters} clusters"));
+    }
+
+    let fat_bytes = fat_sectors * bytes_per_sector;
+    let first_fat = disk.slice(reserved * bytes_per_sector, fat_bytes)?;
+    for copy in 1..fat_count {
+        let other = disk.slice((reserved + copy * fat_sectors) * bytes_per_sector, fat_bytes)?;
+        // Only the entries for existing clusters matter
+        let used = ((clusters + 2) * 4) as usize;
+        if first_fat[..used] != other[..used] {
+            report.problem(format!("FAT copy {copy} differs from FAT 0"));
+        }
+    }
+
+    let vol = Volume {
+        disk,
+        cluster_bytes: sectors_per_cluster * bytes_per_sector,
+        data_start: data_sector * bytes_per_sector,
+        fat: first_fat[..((clusters + 2) * 4) as usize].as_chunks::<4>().0.iter().map(|e| u32::from_le_bytes(*e)).collect(),
+        cluster_end: (clusters + 2) as u32,
+    };
+
+    let mut owners: Vec<Option<usize>> = vec![None; vol.cluster_end as usize];
+    let mut paths = vec![String::from("/")];
+    let mut dirs = vec![(0usize, root_cluster)];
+
+    while let Some((dir, start)) = dirs.pop() {
+        let chain = walk_chain(&vol, &mut owners, &paths, dir, start, report);
+        'clusters: for cluster in chain {
+            let data = vol.read_cluster(cluster)?;
+            for entry in data.as_chunks::<DIRENT_SIZE>().0 {
+                match entry[0] {
+                    0x00 => break 'clusters,
+                    0xE5 => continue,
+                    _ => {}
+                }
+                let attr = entry[11];
+                if attr == ATTR_LONG_NAME || attr & ATTR_VOLUME_ID != 0 {
+                    continue;
+                }
+                let name = short_name(&entry[..11]);
+                if name == "." || name == ".." {
+                    continue;
+                }
+                let first = (le16(entry, 20) as u32) << 16 | le16(entry, 26) as u32;
+                let size = le32(entry, 28) as u64;
+                let path = format!("{}{name}{}", paths[dir], if attr & ATTR_DIRECTORY != 0 { "/" } else { "" });
+
+                if first == 0 {
+                    if attr & ATTR_DIRECTORY != 0 {
+                        report.problem(format!("{path}: directory without clusters"));
+                    } else if size != 0 {
+                        report.problem(format!("{path}: {size} bytes but no clusters"));
+                    }
+                    continue;
+                }
+                paths.push(path);
+                let file = paths.len() - 1;
+                if attr & ATTR_DIRECTORY != 0 {
+                    dirs.push((file, first));
+                    continue;
+                }
+                let chain = walk_chain(&vol, &mut owners, &paths, file, first, report);
+                let needed = size.div_ceil(vol.cluster_bytes).max(1);
+                if chain.len() as u64 != needed {
+                    report.problem(format!(
+                        "{}: size {size} needs {needed} cluster(s), chain has {}",
+                        paths[file],
+                        chain.len()
+                    ));
+                }
+            }
+        }
+    }
+
+    let lost: Vec<u64> = (2..vol.cluster_end)
+        .filter(|&c| owners[c as usize].is_none() && !matches!(vol.entry(c), FREE | BAD))
+        .map(u64::from)
+        .collect();
+    report.numbers("lost clusters (allocated, not reachable)", &lost);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 512-byte sectors, 1 sector per cluster, 32 reserved sectors, one
+    /// one-sector FAT and 64 data clusters; root directory in cluster 2.
+    fn image() -> Vec<u8> {
+        let mut img = vec![0u8; (33 + 64) * 512];
+        img[11..13].copy_from_slice(&512u16.to_le_bytes());
+        img[13] = 1;
+        img[14..16].copy_from_slice(&32u16.to_le_bytes());
+        img[16] = 1;
+        img[32..36].copy_from_slice(&(33u32 + 64).to_le_bytes());
+        img[36..40].copy_from_slice(&1u32.to_le_bytes());
+        img[44..48].copy_from_slice(&2u32.to_le_bytes());
+        img[82..90].copy_from_slice(b"FAT32   ");
+        img[510..512].copy_from_slice(&[0x55, 0xAA]);
+        img
+    }
+
+    fn set_fat(img: &mut [u8], cluster: usize, value: u32) {
+        let off = 32 * 512 + cluster * 4;
+        img[off..off + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn add_file(img: &mut [u8], slot: usize, name: &[u8; 11], first: u16, size: u32) {
+        let off = 33 * 512 + slot * DIRENT_SIZE;
+        img[off..off + 11].copy_from_slice(name);
+        img[off + 26..off + 28].copy_from_slice(&first.to_le_bytes());
+        img[off + 28..off + 32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    #[test]
+    fn test_cross_link_and_lost_cluster() {
+        let mut img = image();
+        set_fat(&mut img, 2, END_OF_CHAIN);
+        // A: 3 -> 4, B: 5 -> 4 (cross-linked), 9 allocated but unreachable
+        set_fat(&mut img, 3, 4);
+        set_fat(&mut img, 4, END_OF_CHAIN);
+        set_fat(&mut img, 5, 4);
+        set_fat(&mut img, 9, END_OF_CHAIN);
+        add_file(&mut img, 0, b"A       TXT", 3, 1024);
+        add_file(&mut img, 1, b"B       TXT", 5, 1024);
+
+        let disk = Disk(&img);
+        assert!(detect(disk));
+        let mut report = Report::default();
+        check(disk, &mut report).unwrap();
+        assert_eq!(report.problems.len(), 3, "{:?}", report.problems);
+        assert!(report.problems[0].contains("/B.TXT: cross-linked with /A.TXT at cluster 4"));
+        assert!(report.problems[1].contains("/B.TXT: size 1024 needs 2 cluster(s), chain has 1"));
+        assert_eq!(report.problems[2], "1 lost clusters (allocated, not reachable): 9");
+    }
+}
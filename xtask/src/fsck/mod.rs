@@ -0,0 +1,154 @@
+//! `cargo xtask fsck`: read-only consistency check of FAT32 and ext2 images.
+//!
+//! Meant for the disk images handed to QEMU through `OS_DISKS`: run it
+//! before booting and again after a test that killed the VM mid-write. The
+//! image is never modified; problems are listed and the command exits
+//! non-zero.
+
+mod ext2;
+mod fat32;
+
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: cargo xtask fsck IMAGE
+
+Checks a raw FAT32 or ext2 volume image (no partition table) for lost and
+cross-linked clusters/blocks, bitmap and free-count mismatches, bad link
+counts and broken directory entries. Nothing is written.";
+
+/// Longest list printed for one kind of problem
+const MAX_LISTED: usize = 10;
+
+/// Bounds-checked little-endian reads from the image
+#[derive(Clone, Copy)]
+pub struct Disk<'a>(&'a [u8]);
+
+impl<'a> Disk<'a> {
+    pub fn slice(&self, off: u64, len: u64) -> Result<&'a [u8], String> {
+        usize::try_from(off)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(off, len)| self.0.get(off..off.checked_add(len)?))
+            .ok_or_else(|| format!("image truncated: {len} bytes at offset {off:#x}"))
+    }
+
+    pub fn u8(&self, off: u64) -> Result<u8, String> {
+        self.slice(off, 1).map(|b| b[0])
+    }
+
+    pub fn u16(&self, off: u64) -> Result<u16, String> {
+        self.slice(off, 2).map(|b| le16(b, 0))
+    }
+
+    pub fn u32(&self, off: u64) -> Result<u32, String> {
+        self.slice(off, 4).map(|b| le32(b, 0))
+    }
+}
+
+pub fn le16(b: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([b[off], b[off + 1]])
+}
+
+pub fn le32(b: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([b[off], b[off + 1], b[off + 2], b[off + 3]])
+}
+
+/// Problems found so far
+#[derive(Default)]
+pub struct Report {
+    problems: Vec<String>,
+    /// Expected after an unclean shutdown; printed, but not a failure
+    warnings: Vec<String>,
+}
+
+impl Report {
+    pub fn problem(&mut self, msg: impl Into<String>) {
+        self.problems.push(msg.into());
+    }
+
+    pub fn warning(&mut self, msg: impl Into<String>) {
+        self.warnings.push(msg.into());
+    }
+
+    /// One line for a whole set of block/cluster numbers.
+    pub fn numbers(&mut self, what: &str, numbers: &[u64]) {
+        if !numbers.is_empty() {
+            self.problem(format!("{} {what}: {}", numbers.len(), ranges(numbers)));
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Formats sorted numbers as `1-4, 9, 12-13`, cut off after `MAX_LISTED` runs.
+fn ranges(numbers: &[u64]) -> String {
+    let mut runs: Vec<(u64, u64)> = Vec::new();
+    for &n in numbers {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == n => *end = n,
+            _ => runs.push((n, n)),
+        }
+    }
+    let mut out: Vec<String> = runs
+        .iter()
+        .take(MAX_LISTED)
+        .map(|&(a, b)| if a == b { a.to_string() } else { format!("{a}-{b}") })
+        .collect();
+    if runs.len() > MAX_LISTED {
+        out.push("...".into());
+    }
+    out.join(", ")
+}
+
+pub fn run(args: &[String]) -> Result<ExitCode, String> {
+    let path = match args {
+        [flag] if flag == "--help" || flag == "-h" => {
+            println!("{USAGE}");
+            return Ok(ExitCode::SUCCESS);
+        }
+        [path] => path,
+        _ => return Err(USAGE.into()),
+    };
+    let data = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+    let disk = Disk(&data);
+    let mut report = Report::default();
+
+    if ext2::detect(disk) {
+        println!("{path}: ext2");
+        ext2::check(disk, &mut report)?;
+    } else if fat32::detect(disk) {
+        println!("{path}: FAT32");
+        fat32::check(disk, &mut report)?;
+    } else {
+        return Err(format!("{path}: neither FAT32 nor ext2"));
+    }
+
+    for warning in &report.warnings {
+        println!("  warning: {warning}");
+    }
+    for problem in &report.problems {
+        println!("  {problem}");
+    }
+    if report.is_clean() {
+        println!("clean");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("{} problem(s)", report.problems.len());
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(ranges(&[1, 2, 3, 4, 9, 12, 13]), "1-4, 9, 12-13");
+        let many: Vec<u64> = (0..40).step_by(2).collect();
+        assert!(ranges(&many).ends_with(", ..."));
+    }
+}
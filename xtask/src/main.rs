@@ -0,0 +1,30 @@
+//! Host-side developer tasks: `cargo xtask <command>`.
+//!
+//! - `size`: section and per-crate size report of the kernel ELF, compared
+//!   against a stored baseline; fails when the kernel grew too much.
+//! - `fsck`: read-only consistency check of a FAT32 or ext2 disk image.
+
+mod elf;
+mod fsck;
+mod size;
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("size") => size::run(&args[1..]),
+        Some("fsck") => fsck::run(&args[1..]),
+        _ => {
+            eprintln!("usage: cargo xtask <size|fsck> [options]   (see `cargo xtask <command> --help`)");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("xtask: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! `cargo xtask size`: kernel size and section bloat report.
+//!
+//! Sums the allocated sections of the kernel ELF and attributes function
+//! and object symbols to crates by their mangled name (legacy `_ZN` and v0
+//! `_R` schemes). Both are compared against a baseline file; the command
+//! fails if the allocated total grew by more than `--max-growth` percent.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use crate::elf::Elf;
+
+const USAGE: &str = "\
+usage: cargo xtask size [--kernel PATH] [--baseline PATH] [--max-growth PCT] [--save]
+
+  --kernel PATH      kernel ELF (default: target/x86_64-unknown-none/debug/os)
+  --baseline PATH    baseline file (default: os/size-baseline.txt)
+  --max-growth PCT   allowed growth of allocated bytes (default: 5)
+  --save             write the current sizes as the new baseline";
+
+/// Crates listed individually; the rest are summed as "other crates"
+const TOP_CRATES: usize = 15;
+
+/// Symbols without Rust mangling (asm, compiler builtins, linker script)
+const UNMANGLED: &str = "[unmangled]";
+
+#[derive(Default)]
+struct Sizes {
+    sections: BTreeMap<String, u64>,
+    crates: BTreeMap<String, u64>,
+}
+
+impl Sizes {
+    fn of(elf: &Elf) -> Self {
+        let mut sizes = Sizes::default();
+        for section in elf.sections.iter().filter(|s| s.alloc) {
+            *sizes.sections.entry(section.name.clone()).or_default() += section.size;
+        }
+        for symbol in &elf.symbols {
+            let name = crate_of(&symbol.name).unwrap_or(UNMANGLED);
+            *sizes.crates.entry(name.to_string()).or_default() += symbol.size;
+        }
+        sizes
+    }
+
+    fn total(&self) -> u64 {
+        self.sections.values().sum()
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut sizes = Sizes::default();
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let mut parts = line.split_whitespace();
+            let (Some(kind), Some(name), Some(Ok(size))) =
+                (parts.next(), parts.next(), parts.next().map(str::parse))
+            else {
+                continue;
+            };
+            match kind {
+                "section" => sizes.sections.insert(name.to_string(), size),
+                "crate" => sizes.crates.insert(name.to_string(), size),
+                _ => None,
+            };
+        }
+        sizes
+    }
+
+    fn serialize(&self) -> String {
+        let mut out = String::from("# cargo xtask size --save\n");
+        for (name, size) in &self.sections {
+            out += &format!("section {name} {size}\n");
+        }
+        for (name, size) in &self.crates {
+            out += &format!("crate {name} {size}\n");
+        }
+        out
+    }
+}
+
+/// Crate name of a mangled Rust symbol.
+fn crate_of(symbol: &str) -> Option<&str> {
+    if let Some(rest) = symbol.strip_prefix("_ZN") {
+        // Legacy: _ZN<len><ident>...
+        return length_prefixed(rest).map(|(ident, _)| ident);
+    }
+    // v0: the crate root is `C`, an optional `s<base62>_` disambiguator,
+    // then <len><ident>
+    let rest = symbol.strip_prefix("_R")?;
+    let bytes = rest.as_bytes();
+    (0..bytes.len()).filter(|&i| bytes[i] == b'C').find_map(|i| {
+        let mut tail = &rest[i + 1..];
+        if let Some(after) = tail.strip_prefix('s') {
+            let end = after.find('_')?;
+            if !after[..end].bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return None;
+            }
+            tail = &after[end + 1..];
+        }
+        length_prefixed(tail).map(|(ident, _)| ident)
+    })
+}
+
+/// Splits `<decimal len><ident of that len>` off the front of `s`.
+fn length_prefixed(s: &str) -> Option<(&str, &str)> {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    let len: usize = s[..digits].parse().ok()?;
+    let ident = s.get(digits..digits + len)?;
+    ident
+        .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        .then(|| (ident, &s[digits + len..]))
+}
+
+fn delta(now: u64, before: Option<u64>) -> String {
+    match before {
+        Some(before) => format!("{:+}", now as i64 - before as i64),
+        None => "new".to_string(),
+    }
+}
+
+pub fn run(args: &[String]) -> Result<ExitCode, String> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf();
+    let mut kernel: Option<PathBuf> = None;
+    let mut baseline_path = root.join("os").join("size-baseline.txt");
+    let mut max_growth = 5.0f64;
+    let mut save = false;
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--kernel" => kernel = Some(PathBuf::from(value()?)),
+            "--baseline" => baseline_path = PathBuf::from(value()?),
+            "--max-growth" => {
+                max_growth = value()?.parse().map_err(|_| "--max-growth takes a number".to_string())?
+            }
+            "--save" => save = true,
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return Ok(ExitCode::SUCCESS);
+            }
+            other => return Err(format!("unknown option {other}\n{USAGE}")),
+        }
+    }
+
+    let kernel = kernel
+        .unwrap_or_else(|| root.join("target").join("x86_64-unknown-none").join("debug").join("os"));
+    let data = std::fs::read(&kernel).map_err(|e| format!("{}: {e}", kernel.display()))?;
+    let elf = Elf::parse(&data).map_err(|e| e.to_string())?;
+    let now = Sizes::of(&elf);
+
+    let baseline = std::fs::read_to_string(&baseline_path).ok().map(|t| Sizes::parse(&t));
+    let before = |map: fn(&Sizes) -> &BTreeMap<String, u64>, name: &str| {
+        baseline.as_ref().and_then(|b| map(b).get(name).copied())
+    };
+
+    println!("kernel: {}", kernel.display());
+    println!("\n{:<24} {:>10} {:>10}", "section", "bytes", "delta");
+    for (name, &size) in &now.sections {
+        println!("{:<24} {:>10} {:>10}", name, size, delta(size, before(|s| &s.sections, name)));
+    }
+
+    let mut crates: Vec<_> = now.crates.iter().collect();
+    crates.sort_by(|a, b| b.1.cmp(a.1));
+    println!("\n{:<24} {:>10} {:>10}", "crate", "bytes", "delta");
+    for (name, &size) in crates.iter().take(TOP_CRATES) {
+        println!("{:<24} {:>10} {:>10}", name, size, delta(size, before(|s| &s.crates, name)));
+    }
+    let rest: u64 = crates.iter().skip(TOP_CRATES).map(|(_, &s)| s).sum();
+    if rest > 0 {
+        println!("{:<24} {:>10}", "other crates", rest);
+    }
+
+    let total = now.total();
+    println!("\nallocated total: {total} bytes");
+
+    if save {
+        std::fs::write(&baseline_path, now.serialize())
+            .map_err(|e| format!("{}: {e}", baseline_path.display()))?;
+        println!("baseline saved to {}", baseline_path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let Some(baseline) = baseline else {
+        println!("no baseline at {} (create one with --save)", baseline_path.display());
+        return Ok(ExitCode::SUCCESS);
+    };
+    let base_total = baseline.total();
+    let growth = (total as f64 - base_total as f64) * 100.0 / base_total.max(1) as f64;
+    println!("baseline total:  {base_total} bytes ({growth:+.2}%)");
+    if growth > max_growth {
+        eprintln!("kernel grew {growth:.2}%, more than the allowed {max_growth}%");
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crate_of() {
+        assert_eq!(crate_of("_ZN4core3fmt5write17h0123456789abcdefE"), Some("core"));
+        assert_eq!(crate_of("_RNvNtCs1234abcd_4core3fmt5write"), Some("core"));
+        assert_eq!(crate_of("_RNvCs9_2os11kernel_main"), Some("os"));
+        assert_eq!(crate_of("memcpy"), None);
+    }
+}